@@ -8,9 +8,16 @@
 
 mod page;
 mod pin_slab;
+#[cfg(target_os = "linux")]
+mod reactor;
+mod rng;
 mod scheduler;
+mod timer;
 mod waker64;
 
+#[cfg(test)]
+mod test_utils;
+
 //==============================================================================
 // Imports
 //==============================================================================
@@ -21,9 +28,56 @@ extern crate test;
 // Exports
 //==============================================================================
 
-pub use crate::scheduler::{
-    FutureResult,
-    Scheduler,
-    SchedulerFuture,
-    SchedulerHandle,
+#[cfg(target_os = "linux")]
+pub use crate::reactor::{
+    Completion,
+    EpollReactor,
+    Parker,
+    Readiness,
+    Unparker,
+    UringReactor,
+};
+pub use crate::{
+    scheduler::{
+        consume_budget,
+        has_budget_remaining,
+        ChaosConfig,
+        Decision,
+        FutureResult,
+        IdleAction,
+        IdlePolicy,
+        IdleStats,
+        Join,
+        JoinNext,
+        JoinSet,
+        LeakReport,
+        PageLeak,
+        PollerContext,
+        PollerId,
+        PollerPhase,
+        Recording,
+        Scheduler,
+        SchedulerFuture,
+        SchedulerHandle,
+        SlowPoll,
+        TaskAttributes,
+        TaskDescription,
+        TaskFlag,
+        TaskGroup,
+        TaskNode,
+        TaskStats,
+        Violation,
+        Wait,
+    },
+    timer::{
+        Clock,
+        Elapsed,
+        Interval,
+        ManualClock,
+        Sleep,
+        SystemClock,
+        Tick,
+        Timeout,
+        Timer,
+    },
 };
@@ -23,9 +23,10 @@ pub const WAKER_PAGE_SIZE: usize = 64;
 
 /// Waker Page
 ///
-/// This structure holds the status of multiple futures in the scheduler. It is
-/// composed by 3 bitmaps, each of which having the ith bit to represent some
-/// state for the ith future.
+/// This structure holds the status of multiple futures in the scheduler. Next
+/// to its reference count and the number of handles to its futures, it is
+/// composed by 4 bitmaps (notified, completed, dropped and suspended), each of
+/// which having the ith bit to represent some state for the ith future.
 ///
 /// The number of bytes in this structure should match the number of bits in a
 /// [Waker64]. Furthermore, the structure should be aligned in memory with its
@@ -33,19 +34,23 @@ pub const WAKER_PAGE_SIZE: usize = 64;
 /// scheduler, so that it may cast back a raw pointer and operate on a specific
 /// future whenever needed.
 ///
-/// TODO: use the unused space in this structure to something useful.
+/// TODO: use the remaining 16 bytes of padding to something useful.
 #[repr(align(64))]
 pub struct WakerPage {
     /// Reference count for the page.
     refcount: Waker64,
+    /// Number of handles to futures in the page.
+    handles: Waker64,
     /// Flags wether or not a given future has been notified.
     notified: Waker64,
     /// Flags whether or not a given future has completed.
     completed: Waker64,
     /// Flags whether or not a given future has ben dropped.
     dropped: Waker64,
+    /// Flags whether or not a given future is suspended.
+    suspended: Waker64,
     /// Padding required to make the structure 64-byte big.
-    _unused: [u8; 32],
+    _unused: [u8; 16],
 }
 
 //==============================================================================
@@ -60,6 +65,17 @@ impl WakerPage {
         self.notified.fetch_or(1 << ix);
     }
 
+    /// Sets the notification flags for the futures in the target [WakerPage] that are flagged in `mask`.
+    pub fn notify_many(&self, mask: u64) {
+        self.notified.fetch_or(mask);
+    }
+
+    /// Queries whether or not the notification flag for the `ix` future in the target [WakerPage] is set.
+    pub fn is_notified(&self, ix: usize) -> bool {
+        debug_assert!(ix < WAKER_BIT_LENGTH);
+        self.notified.load() & (1 << ix) != 0
+    }
+
     /// Takes out notification flags in the target [WakerPage].
     /// Notification flags are reset after this operation.
     pub fn take_notified(&self) -> u64 {
@@ -68,7 +84,16 @@ impl WakerPage {
         let mut notified = self.notified.swap(0);
         notified &= !self.completed.load();
         notified &= !self.dropped.load();
-        notified
+        // Hold notifications of suspended futures back until they are resumed.
+        let suspended: u64 = notified & self.suspended.load();
+        self.notified.fetch_or(suspended);
+        notified & !suspended
+    }
+
+    /// Queries whether or not some future in the target [WakerPage] that has neither completed, been dropped nor been
+    /// suspended is notified. Notification flags are left unmodified.
+    pub fn has_notified(&self) -> bool {
+        self.notified.load() & !self.completed.load() & !self.dropped.load() & !self.suspended.load() != 0
     }
 
     /// Queries whether or not the completed flag for the `ix` future in the target [WakerPage] is set.
@@ -77,10 +102,17 @@ impl WakerPage {
         self.completed.load() & (1 << ix) != 0
     }
 
+    /// Gets the completed flags in the target [WakerPage].
+    /// Completed flags are left unmodified.
+    pub fn get_completed(&self) -> u64 {
+        self.completed.load()
+    }
+
     /// Sets the completed flag for the `ix` future in the target [WakerPage].
     pub fn mark_completed(&self, ix: usize) {
         debug_assert!(ix < WAKER_BIT_LENGTH);
         self.completed.fetch_or(1 << ix);
+        self.notified.fetch_and(!(1 << ix));
     }
 
     /// Sets the dropped flag for the `ix` future in the target [WakerPage].
@@ -101,13 +133,34 @@ impl WakerPage {
         self.dropped.load() & (1 << ix) != 0
     }
 
+    /// Sets the suspended flag for the `ix` future in the target [WakerPage].
+    pub fn suspend(&self, ix: usize) {
+        debug_assert!(ix < WAKER_BIT_LENGTH);
+        self.suspended.fetch_or(1 << ix);
+    }
+
+    /// Clears the suspended flag for the `ix` future in the target [WakerPage].
+    /// Notifications received while suspended are left in place.
+    pub fn resume(&self, ix: usize) {
+        debug_assert!(ix < WAKER_BIT_LENGTH);
+        self.suspended.fetch_and(!(1 << ix));
+    }
+
+    /// Queries whether or not the suspended flag for the `ix` future in the target [WakerPage] is set.
+    pub fn is_suspended(&self, ix: usize) -> bool {
+        debug_assert!(ix < WAKER_BIT_LENGTH);
+        self.suspended.load() & (1 << ix) != 0
+    }
+
     /// Resets all flags in the target [WakerPage].
     /// The reference count for the target page is reset to one.
     pub fn reset(&mut self) {
         self.refcount.swap(1);
+        self.handles.swap(0);
         self.notified.swap(0);
         self.completed.swap(0);
         self.dropped.swap(0);
+        self.suspended.swap(0);
     }
 
     /// Initialize flags for the `ix` future in the target [WakerPage].
@@ -117,6 +170,7 @@ impl WakerPage {
         self.notified.fetch_or(1 << ix);
         self.completed.fetch_and(!(1 << ix));
         self.dropped.fetch_and(!(1 << ix));
+        self.suspended.fetch_and(!(1 << ix));
     }
 
     /// Clears flags for the `ix` future in the target [WakerPage]
@@ -127,6 +181,7 @@ impl WakerPage {
         self.notified.fetch_and(mask);
         self.completed.fetch_and(mask);
         self.dropped.fetch_and(mask);
+        self.suspended.fetch_and(mask);
     }
 
     /// Increments the reference count of the target [WakerPage].
@@ -142,10 +197,24 @@ impl WakerPage {
     }
 
     /// Gets the reference count of the target [WakerPage].
-    #[cfg(test)]
     pub fn refcount_get(&self) -> u64 {
         self.refcount.load()
     }
+
+    /// Increments the number of handles to futures in the target [WakerPage].
+    pub fn handles_inc(&self) {
+        self.handles.fetch_add(1);
+    }
+
+    /// Decrements the number of handles to futures in the target [WakerPage].
+    pub fn handles_dec(&self) {
+        self.handles.fetch_sub(1);
+    }
+
+    /// Gets the number of handles to futures in the target [WakerPage].
+    pub fn handles_get(&self) -> u64 {
+        self.handles.load()
+    }
 }
 
 //==============================================================================
@@ -157,9 +226,11 @@ impl Default for WakerPage {
     fn default() -> Self {
         Self {
             refcount: Waker64::new(1),
+            handles: Waker64::new(0),
             notified: Waker64::new(0),
             completed: Waker64::new(0),
             dropped: Waker64::new(0),
+            suspended: Waker64::new(0),
             _unused: Default::default(),
         }
     }
@@ -189,6 +260,48 @@ mod tests {
         assert_eq!(mem::size_of::<WakerPage>(), WAKER_PAGE_SIZE);
     }
 
+    #[test]
+    fn test_handles() {
+        let mut pg: WakerPage = WakerPage::default();
+        pg.handles_inc();
+        pg.handles_inc();
+        pg.handles_dec();
+        assert_eq!(pg.handles_get(), 1);
+        assert_eq!(pg.refcount_get(), 1);
+
+        pg.reset();
+        assert_eq!(pg.handles_get(), 0);
+    }
+
+    #[test]
+    fn test_suspended() {
+        let pg: WakerPage = WakerPage::default();
+        pg.initialize(3);
+        pg.suspend(3);
+        assert!(!pg.has_notified());
+        assert_eq!(pg.take_notified(), 0);
+
+        // Notifications are delivered once resumed.
+        pg.resume(3);
+        assert!(pg.has_notified());
+        assert_eq!(pg.take_notified(), 1 << 3);
+    }
+
+    #[test]
+    fn test_completed() {
+        let pg: WakerPage = WakerPage::default();
+        pg.initialize(3);
+        pg.mark_completed(3);
+        assert!(!pg.is_notified(3));
+
+        // Stale notifications of completed futures are never taken out.
+        pg.notify(3);
+        pg.notify_many(1 << 3);
+        assert!(pg.is_notified(3));
+        assert!(!pg.has_notified());
+        assert_eq!(pg.take_notified(), 0);
+    }
+
     #[bench]
     fn bench_notify(b: &mut Bencher) {
         let pg: WakerPage = WakerPage::default();
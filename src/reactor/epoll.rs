@@ -0,0 +1,650 @@
+// Copyright (c) Microsoft Corporation.
+// Licensed under the MIT license.
+
+//! Epoll reactor.
+//!
+//! File descriptors are registered in level-triggered mode, and only for the
+//! directions that some task is waiting on. A file descriptor that no task
+//! waits on is removed from the epoll set, so that a hang-up that nobody
+//! observes does not keep on firing. When a file descriptor becomes ready, the
+//! reactor records the readiness and wakes the waiting tasks. The readiness is
+//! consumed by the first [Readiness] future that observes it, so a task that
+//! hits `EAGAIN` afterwards should simply wait again.
+
+//==============================================================================
+// Imports
+//==============================================================================
+
+use crate::{
+    consume_budget,
+    Scheduler,
+    SchedulerHandle,
+};
+use ::std::{
+    cell::{
+        RefCell,
+        RefMut,
+    },
+    collections::HashMap,
+    future::Future,
+    io,
+    os::unix::io::RawFd,
+    pin::Pin,
+    rc::Rc,
+    task::{
+        Context,
+        Poll,
+        Waker,
+    },
+    time::Duration,
+};
+
+//==============================================================================
+// Constants
+//==============================================================================
+
+/// Maximum number of events that are harvested by a single call to `epoll_wait`.
+const MAX_EVENTS: usize = 64;
+
+/// Events that make a file descriptor readable.
+const READ_EVENTS: u32 = (libc::EPOLLIN | libc::EPOLLRDHUP | libc::EPOLLHUP | libc::EPOLLERR) as u32;
+
+/// Events that make a file descriptor writable.
+const WRITE_EVENTS: u32 = (libc::EPOLLOUT | libc::EPOLLHUP | libc::EPOLLERR) as u32;
+
+//==============================================================================
+// Structures
+//==============================================================================
+
+/// Direction of Readiness
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Direction {
+    Read,
+    Write,
+}
+
+/// Registration of a File Descriptor
+#[derive(Default)]
+struct Registration {
+    /// Events that the file descriptor is registered for. If this is zero, the file descriptor is not in the epoll set.
+    interest: u32,
+    /// Whether or not the file descriptor was reported readable.
+    readable: bool,
+    /// Whether or not the file descriptor was reported writable.
+    writable: bool,
+    /// Wakers of the tasks waiting for the file descriptor to become readable, by the identifier of their futures.
+    read_wakers: Vec<(u64, Waker)>,
+    /// Wakers of the tasks waiting for the file descriptor to become writable, by the identifier of their futures.
+    write_wakers: Vec<(u64, Waker)>,
+}
+
+/// Reactor State
+struct Inner {
+    /// Epoll file descriptor.
+    epfd: RawFd,
+    /// Registered file descriptors.
+    registrations: HashMap<RawFd, Registration>,
+    /// Buffer for harvested events.
+    events: Vec<libc::epoll_event>,
+    /// Identifier of the next readiness future.
+    next_id: u64,
+}
+
+/// Epoll Reactor
+#[derive(Clone)]
+pub struct EpollReactor {
+    inner: Rc<RefCell<Inner>>,
+}
+
+/// Readiness Future
+///
+/// This future completes once a file descriptor is ready in some direction.
+pub struct Readiness {
+    /// Reactor that drives this future.
+    reactor: EpollReactor,
+    /// Target file descriptor.
+    fd: RawFd,
+    /// Target direction.
+    direction: Direction,
+    /// Identifier of this future, which tells its waker apart from those of other futures.
+    id: u64,
+}
+
+//==============================================================================
+// Associate Functions
+//==============================================================================
+
+/// Associate Functions for Registrations
+impl Registration {
+    /// Consumes the readiness of the target [Registration] in `direction`, if any.
+    fn take_ready(&mut self, direction: Direction) -> bool {
+        match direction {
+            Direction::Read => ::std::mem::take(&mut self.readable),
+            Direction::Write => ::std::mem::take(&mut self.writable),
+        }
+    }
+
+    /// Returns the wakers of the target [Registration] for `direction`.
+    fn wakers(&mut self, direction: Direction) -> &mut Vec<(u64, Waker)> {
+        match direction {
+            Direction::Read => &mut self.read_wakers,
+            Direction::Write => &mut self.write_wakers,
+        }
+    }
+
+    /// Sets `waker` as the waker of the future `id` in the target [Registration] for `direction`.
+    fn add_waker(&mut self, direction: Direction, id: u64, waker: &Waker) {
+        let wakers: &mut Vec<(u64, Waker)> = self.wakers(direction);
+        match wakers.iter_mut().find(|(old_id, _)| *old_id == id) {
+            Some((_, old)) if old.will_wake(waker) => (),
+            Some((_, old)) => *old = waker.clone(),
+            None => wakers.push((id, waker.clone())),
+        }
+    }
+
+    /// Removes the waker of the future `id` from the target [Registration] for `direction`, returning whether or not
+    /// it was there.
+    fn remove_waker(&mut self, direction: Direction, id: u64) -> bool {
+        let wakers: &mut Vec<(u64, Waker)> = self.wakers(direction);
+        let len: usize = wakers.len();
+        wakers.retain(|(old_id, _)| *old_id != id);
+        wakers.len() != len
+    }
+
+    /// Computes the events that the target [Registration] should be registered for.
+    fn wanted_interest(&self) -> u32 {
+        let mut interest: u32 = 0;
+        if !self.read_wakers.is_empty() {
+            interest |= (libc::EPOLLIN | libc::EPOLLRDHUP) as u32;
+        }
+        if !self.write_wakers.is_empty() {
+            interest |= libc::EPOLLOUT as u32;
+        }
+        interest
+    }
+}
+
+/// Associate Functions for Reactor State
+impl Inner {
+    /// Registers `fd` for the events that its waiting tasks want, if it is not already. A file descriptor that no task
+    /// waits on is removed from the epoll set.
+    fn update_interest(&mut self, fd: RawFd) -> io::Result<()> {
+        let registration: &mut Registration = self.registrations.get_mut(&fd).expect("unregistered file descriptor");
+        let interest: u32 = registration.wanted_interest();
+        if interest == registration.interest {
+            return Ok(());
+        }
+        let op: libc::c_int = match (registration.interest, interest) {
+            (0, _) => libc::EPOLL_CTL_ADD,
+            (_, 0) => libc::EPOLL_CTL_DEL,
+            _ => libc::EPOLL_CTL_MOD,
+        };
+        let mut event: libc::epoll_event = libc::epoll_event {
+            events: interest,
+            u64: fd as u64,
+        };
+        if unsafe { libc::epoll_ctl(self.epfd, op, fd, &mut event) } == -1 {
+            return Err(io::Error::last_os_error());
+        }
+        registration.interest = interest;
+        Ok(())
+    }
+}
+
+/// Associate Functions for Epoll Reactors
+impl EpollReactor {
+    /// Creates an epoll reactor.
+    pub fn new() -> io::Result<Self> {
+        let epfd: RawFd = unsafe { libc::epoll_create1(libc::EPOLL_CLOEXEC) };
+        if epfd == -1 {
+            return Err(io::Error::last_os_error());
+        }
+        let inner: Inner = Inner {
+            epfd,
+            registrations: HashMap::new(),
+            events: Vec::with_capacity(MAX_EVENTS),
+            next_id: 0,
+        };
+        Ok(Self {
+            inner: Rc::new(RefCell::new(inner)),
+        })
+    }
+
+    /// Returns a future that completes once `fd` is readable. The file descriptor is registered in the target
+    /// [EpollReactor] if it is not already.
+    pub fn readable(&self, fd: RawFd) -> Readiness {
+        self.readiness(fd, Direction::Read)
+    }
+
+    /// Returns a future that completes once `fd` is writable. The file descriptor is registered in the target
+    /// [EpollReactor] if it is not already.
+    pub fn writable(&self, fd: RawFd) -> Readiness {
+        self.readiness(fd, Direction::Write)
+    }
+
+    /// Removes `fd` from the target [EpollReactor]. This must be done before the file descriptor is closed. Tasks
+    /// waiting on the file descriptor are woken up.
+    pub fn deregister(&self, fd: RawFd) -> io::Result<()> {
+        let registration: Option<Registration> = {
+            let mut inner: RefMut<Inner> = self.inner.borrow_mut();
+            let registration: Option<Registration> = inner.registrations.remove(&fd);
+            if matches!(registration.as_ref(), Some(registration) if registration.interest != 0)
+                && unsafe { libc::epoll_ctl(inner.epfd, libc::EPOLL_CTL_DEL, fd, ::std::ptr::null_mut()) } == -1
+            {
+                return Err(io::Error::last_os_error());
+            }
+            registration
+        };
+        if let Some(registration) = registration {
+            for (_, waker) in registration.read_wakers.into_iter().chain(registration.write_wakers) {
+                waker.wake();
+            }
+        }
+        Ok(())
+    }
+
+    /// Returns the number of file descriptors registered in the target [EpollReactor].
+    pub fn len(&self) -> usize {
+        self.inner.borrow().registrations.len()
+    }
+
+    /// Queries whether or not no file descriptor is registered in the target [EpollReactor].
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Waits for events for at most `timeout`, or indefinitely if `timeout` is `None`, and wakes up the tasks that
+    /// are waiting on them. The number of harvested events is returned. If some file descriptor fails to be updated,
+    /// all tasks are still woken up and the first error is returned.
+    pub fn wait(&self, timeout: Option<Duration>) -> io::Result<usize> {
+        let timeout_ms: libc::c_int = match timeout {
+            // Round up, so that we do not wake up ahead of a deadline.
+            Some(timeout) => {
+                let ms: u128 = timeout.as_millis() + (timeout.subsec_nanos() % 1_000_000 != 0) as u128;
+                ms.min(libc::c_int::MAX as u128) as libc::c_int
+            },
+            None => -1,
+        };
+
+        let mut wakers: Vec<Waker> = Vec::new();
+        let mut error: Option<io::Error> = None;
+        let nevents: usize = {
+            let mut inner: RefMut<Inner> = self.inner.borrow_mut();
+            let inner: &mut Inner = &mut inner;
+            inner.events.clear();
+            let nevents: libc::c_int = unsafe {
+                libc::epoll_wait(
+                    inner.epfd,
+                    inner.events.as_mut_ptr(),
+                    MAX_EVENTS as libc::c_int,
+                    timeout_ms,
+                )
+            };
+            if nevents == -1 {
+                let error: io::Error = io::Error::last_os_error();
+                return match error.kind() {
+                    io::ErrorKind::Interrupted => Ok(0),
+                    _ => Err(error),
+                };
+            }
+            unsafe { inner.events.set_len(nevents as usize) };
+
+            for ix in 0..inner.events.len() {
+                let event: libc::epoll_event = inner.events[ix];
+                let fd: RawFd = event.u64 as RawFd;
+                let registration: &mut Registration = match inner.registrations.get_mut(&fd) {
+                    Some(registration) => registration,
+                    None => continue,
+                };
+                if event.events & READ_EVENTS != 0 {
+                    registration.readable = true;
+                    wakers.extend(registration.read_wakers.drain(..).map(|(_, waker)| waker));
+                }
+                if event.events & WRITE_EVENTS != 0 {
+                    registration.writable = true;
+                    wakers.extend(registration.write_wakers.drain(..).map(|(_, waker)| waker));
+                }
+                // Stop reporting directions that nobody waits on, so that level-triggered events do not spin.
+                if let Err(e) = inner.update_interest(fd) {
+                    error.get_or_insert(e);
+                }
+            }
+            nevents as usize
+        };
+
+        // Wake up tasks outside of the borrow, since wakers may call back into the reactor.
+        wakers.into_iter().for_each(Waker::wake);
+        match error {
+            Some(error) => Err(error),
+            None => Ok(nevents),
+        }
+    }
+
+    /// Runs one iteration of the event loop: polls `scheduler`, and then harvests events. If no task is notified after
+    /// polling, this blocks until some event arrives or the next timer of `scheduler` expires.
+    pub fn turn(&self, scheduler: &Scheduler) -> io::Result<()> {
+        scheduler.poll();
+        self.park(scheduler)
+    }
+
+    /// Runs the event loop until the task of `handle` completes.
+    pub fn run_until(&self, scheduler: &Scheduler, handle: &SchedulerHandle) -> io::Result<()> {
+        loop {
+            scheduler.poll();
+            if handle.has_completed() {
+                return Ok(());
+            }
+            self.park(scheduler)?;
+        }
+    }
+
+    /// Harvests events, blocking only if no task in `scheduler` is notified. Blocking is bounded by the next timer
+    /// of `scheduler`. If the clock of `scheduler` is virtual, this does not block, but moves the clock to the next
+    /// timer instead.
+    fn park(&self, scheduler: &Scheduler) -> io::Result<()> {
+        let timeout: Option<Duration> = if scheduler.has_notified() {
+            Some(Duration::ZERO)
+        } else {
+            scheduler.timer().park_timeout(None)
+        };
+        self.wait(timeout)?;
+        Ok(())
+    }
+
+    /// Returns a future that completes once `fd` is ready in `direction`.
+    fn readiness(&self, fd: RawFd, direction: Direction) -> Readiness {
+        let mut inner: RefMut<Inner> = self.inner.borrow_mut();
+        let id: u64 = inner.next_id;
+        inner.next_id += 1;
+        Readiness {
+            reactor: self.clone(),
+            fd,
+            direction,
+            id,
+        }
+    }
+
+    /// Polls for the readiness of `fd` in `direction`, on behalf of the future `id`.
+    fn poll_ready(&self, fd: RawFd, direction: Direction, id: u64, ctx: &mut Context) -> Poll<io::Result<()>> {
+        let mut inner: RefMut<Inner> = self.inner.borrow_mut();
+        let registration: &mut Registration = inner.registrations.entry(fd).or_default();
+        if registration.take_ready(direction) {
+            registration.remove_waker(direction, id);
+            return Poll::Ready(Ok(()));
+        }
+        registration.add_waker(direction, id, ctx.waker());
+        match inner.update_interest(fd) {
+            Ok(()) => Poll::Pending,
+            Err(e) => {
+                // A file descriptor that cannot be added to the epoll set is not registered.
+                if inner.registrations[&fd].interest == 0 {
+                    inner.registrations.remove(&fd);
+                }
+                Poll::Ready(Err(e))
+            },
+        }
+    }
+}
+
+//==============================================================================
+// Trait Implementations
+//==============================================================================
+
+/// Future Trait Implementation for Readiness Futures
+impl Future for Readiness {
+    type Output = io::Result<()>;
+
+    /// Polls the target [Readiness].
+    fn poll(self: Pin<&mut Self>, ctx: &mut Context) -> Poll<io::Result<()>> {
+        // A file descriptor that is always ready should not monopolize the poll pass.
+        if consume_budget(ctx).is_pending() {
+            return Poll::Pending;
+        }
+        let self_: &mut Readiness = self.get_mut();
+        self_.reactor.poll_ready(self_.fd, self_.direction, self_.id, ctx)
+    }
+}
+
+/// Drop Trait Implementation for Readiness Futures
+impl Drop for Readiness {
+    /// Withdraws the waker of the target [Readiness] from the reactor, so that a future that is dropped before it
+    /// completes, such as one that timed out, no longer keeps its task alive nor its file descriptor in the epoll set.
+    fn drop(&mut self) {
+        let mut inner: RefMut<Inner> = self.reactor.inner.borrow_mut();
+        let withdrawn: bool = match inner.registrations.get_mut(&self.fd) {
+            Some(registration) => registration.remove_waker(self.direction, self.id),
+            None => false,
+        };
+        // Errors cannot be reported from here. The file descriptor then stays in the epoll set until it fires.
+        if withdrawn {
+            let _ = inner.update_interest(self.fd);
+        }
+    }
+}
+
+/// Drop Trait Implementation for Reactor State
+impl Drop for Inner {
+    fn drop(&mut self) {
+        unsafe { libc::close(self.epfd) };
+    }
+}
+
+//==============================================================================
+// Unit Tests
+//==============================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        EpollReactor,
+        Inner,
+        Readiness,
+        Registration,
+    };
+    use crate::{
+        test_utils::TestTask,
+        Elapsed,
+        ManualClock,
+        Scheduler,
+        SchedulerHandle,
+        Timer,
+    };
+    use ::std::{
+        cell::{
+            Ref,
+            RefCell,
+        },
+        io,
+        os::unix::io::RawFd,
+        rc::Rc,
+        time::{
+            Duration,
+            Instant,
+        },
+    };
+
+    fn pipe() -> (RawFd, RawFd) {
+        let mut fds: [RawFd; 2] = [0; 2];
+        assert_eq!(
+            unsafe { libc::pipe2(fds.as_mut_ptr(), libc::O_NONBLOCK | libc::O_CLOEXEC) },
+            0
+        );
+        (fds[0], fds[1])
+    }
+
+    #[test]
+    fn readable_pipe() {
+        let scheduler: Scheduler = Scheduler::default();
+        let reactor: EpollReactor = EpollReactor::new().expect("new() failed");
+        let (rx, tx): (RawFd, RawFd) = pipe();
+
+        let received: Rc<RefCell<Vec<u8>>> = Rc::new(RefCell::new(Vec::new()));
+        let received_: Rc<RefCell<Vec<u8>>> = received.clone();
+        let reactor_: EpollReactor = reactor.clone();
+        let handle: SchedulerHandle = scheduler
+            .insert(TestTask::new(async move {
+                loop {
+                    let mut buf: [u8; 16] = [0; 16];
+                    let n: isize = unsafe { libc::read(rx, buf.as_mut_ptr() as *mut libc::c_void, buf.len()) };
+                    match n {
+                        0 => return,
+                        n if n > 0 => received_.borrow_mut().extend_from_slice(&buf[..n as usize]),
+                        _ => reactor_.readable(rx).await.expect("readable() failed"),
+                    }
+                }
+            }))
+            .expect("insert() failed");
+
+        // Nothing to read yet, so the task waits on the reactor.
+        scheduler.poll();
+        assert_eq!(reactor.len(), 1);
+        assert!(!scheduler.has_notified());
+
+        assert_eq!(
+            unsafe { libc::write(tx, b"hello".as_ptr() as *const libc::c_void, 5) },
+            5
+        );
+        unsafe { libc::close(tx) };
+        reactor.run_until(&scheduler, &handle).expect("run_until() failed");
+        assert_eq!(&*received.borrow(), b"hello");
+
+        reactor.deregister(rx).expect("deregister() failed");
+        assert!(reactor.is_empty());
+        unsafe { libc::close(rx) };
+    }
+
+    #[test]
+    fn writable_pipe() {
+        let scheduler: Scheduler = Scheduler::default();
+        let reactor: EpollReactor = EpollReactor::new().expect("new() failed");
+        let (rx, tx): (RawFd, RawFd) = pipe();
+
+        let reactor_: EpollReactor = reactor.clone();
+        let handle: SchedulerHandle = scheduler
+            .insert(TestTask::new(async move {
+                reactor_.writable(tx).await.expect("writable() failed");
+            }))
+            .expect("insert() failed");
+
+        reactor.run_until(&scheduler, &handle).expect("run_until() failed");
+        reactor.deregister(tx).expect("deregister() failed");
+        unsafe {
+            libc::close(rx);
+            libc::close(tx);
+        }
+    }
+
+    #[test]
+    fn readable_many_waiters() {
+        let scheduler: Scheduler = Scheduler::default();
+        let reactor: EpollReactor = EpollReactor::new().expect("new() failed");
+        let (rx, tx): (RawFd, RawFd) = pipe();
+
+        let handles: Vec<SchedulerHandle> = (0..2)
+            .map(|_| {
+                let reactor_: EpollReactor = reactor.clone();
+                scheduler
+                    .insert(TestTask::new(async move {
+                        reactor_.readable(rx).await.expect("readable() failed");
+                    }))
+                    .expect("insert() failed")
+            })
+            .collect();
+        scheduler.poll();
+
+        // No waiter replaces the other.
+        assert_eq!(
+            unsafe { libc::write(tx, b"hello".as_ptr() as *const libc::c_void, 5) },
+            5
+        );
+        for handle in &handles {
+            reactor.run_until(&scheduler, handle).expect("run_until() failed");
+        }
+
+        reactor.deregister(rx).expect("deregister() failed");
+        unsafe {
+            libc::close(rx);
+            libc::close(tx);
+        }
+    }
+
+    #[test]
+    fn hang_up_without_waiters() {
+        let scheduler: Scheduler = Scheduler::default();
+        let reactor: EpollReactor = EpollReactor::new().expect("new() failed");
+        let (rx, tx): (RawFd, RawFd) = pipe();
+
+        let reactor_: EpollReactor = reactor.clone();
+        let handle: SchedulerHandle = scheduler
+            .insert(TestTask::new(async move {
+                reactor_.readable(rx).await.expect("readable() failed");
+            }))
+            .expect("insert() failed");
+        scheduler.poll();
+        unsafe { libc::close(tx) };
+        reactor.run_until(&scheduler, &handle).expect("run_until() failed");
+
+        // The hang-up is no longer reported once nobody waits on the file descriptor.
+        assert_eq!(reactor.wait(Some(Duration::ZERO)).expect("wait() failed"), 0);
+        reactor.deregister(rx).expect("deregister() failed");
+        unsafe { libc::close(rx) };
+    }
+
+    #[test]
+    fn readable_timed_out() {
+        let clock: Rc<ManualClock> = Rc::new(ManualClock::new());
+        let scheduler: Scheduler = Scheduler::new(clock.clone());
+        let reactor: EpollReactor = EpollReactor::new().expect("new() failed");
+        let (rx, tx): (RawFd, RawFd) = pipe();
+
+        let reactor_: EpollReactor = reactor.clone();
+        let timer: Timer = scheduler.timer();
+        let handle: SchedulerHandle = scheduler
+            .insert(TestTask::new(async move {
+                let readable: Readiness = reactor_.readable(rx);
+                let result: Result<io::Result<()>, Elapsed> = timer.timeout(readable, Duration::from_secs(1)).await;
+                assert_eq!(result.err(), Some(Elapsed));
+            }))
+            .expect("insert() failed");
+        scheduler.poll();
+        assert_ne!(reactor.inner.borrow().registrations[&rx].interest, 0);
+
+        // The pending future is dropped, and so are its waker and its interest in the file descriptor.
+        clock.advance(Duration::from_secs(1));
+        scheduler.poll();
+        assert!(handle.has_completed());
+        let registration_is_idle: bool = {
+            let inner: Ref<Inner> = reactor.inner.borrow();
+            let registration: &Registration = &inner.registrations[&rx];
+            registration.interest == 0 && registration.read_wakers.is_empty()
+        };
+        assert!(registration_is_idle);
+        assert_eq!(
+            unsafe { libc::write(tx, b"hello".as_ptr() as *const libc::c_void, 5) },
+            5
+        );
+        assert_eq!(reactor.wait(Some(Duration::ZERO)).expect("wait() failed"), 0);
+
+        reactor.deregister(rx).expect("deregister() failed");
+        unsafe {
+            libc::close(rx);
+            libc::close(tx);
+        }
+    }
+
+    #[test]
+    fn wait_bounded_by_timer() {
+        let scheduler: Scheduler = Scheduler::default();
+        let reactor: EpollReactor = EpollReactor::new().expect("new() failed");
+        let timer: Timer = scheduler.timer();
+        let handle: SchedulerHandle = scheduler
+            .insert(TestTask::new(async move {
+                timer.sleep(Duration::from_millis(5)).await;
+            }))
+            .expect("insert() failed");
+
+        let start: Instant = Instant::now();
+        reactor.run_until(&scheduler, &handle).expect("run_until() failed");
+        assert!(start.elapsed() >= Duration::from_millis(5));
+    }
+}
@@ -0,0 +1,28 @@
+// Copyright (c) Microsoft Corporation.
+// Licensed under the MIT license.
+
+//! I/O reactors that bridge readiness and completion of file descriptors to
+//! the wakers of tasks in a [crate::Scheduler].
+
+mod epoll;
+mod park;
+mod uring;
+
+//==============================================================================
+// Exports
+//==============================================================================
+
+pub use self::{
+    epoll::{
+        EpollReactor,
+        Readiness,
+    },
+    park::{
+        Parker,
+        Unparker,
+    },
+    uring::{
+        Completion,
+        UringReactor,
+    },
+};
@@ -0,0 +1,199 @@
+// Copyright (c) Microsoft Corporation.
+// Licensed under the MIT license.
+
+//! Eventfd-backed parking.
+//!
+//! A [Parker] puts the thread that owns it to sleep on an eventfd, and an
+//! [Unparker], which may be sent to other threads, wakes it up. The eventfd is
+//! only written to when the owner is actually parked, so unparking a running
+//! thread costs no system call.
+
+//==============================================================================
+// Imports
+//==============================================================================
+
+use ::std::{
+    io,
+    os::unix::io::RawFd,
+    ptr,
+    sync::{
+        atomic::{
+            AtomicBool,
+            Ordering,
+        },
+        Arc,
+    },
+    time::Duration,
+};
+
+//==============================================================================
+// Structures
+//==============================================================================
+
+/// State Shared by Parkers and Unparkers
+struct Inner {
+    /// Eventfd that parked threads sleep on.
+    eventfd: RawFd,
+    /// Whether or not the owner is parked, or about to park.
+    parked: AtomicBool,
+    /// Whether or not the owner was unparked since it last parked.
+    notified: AtomicBool,
+}
+
+/// Parker
+pub struct Parker {
+    inner: Arc<Inner>,
+}
+
+/// Unparker
+///
+/// This structure wakes up the thread that parks on the corresponding [Parker].
+#[derive(Clone)]
+pub struct Unparker {
+    inner: Arc<Inner>,
+}
+
+//==============================================================================
+// Associate Functions
+//==============================================================================
+
+/// Associate Functions for Parkers
+impl Parker {
+    /// Creates a parker.
+    pub fn new() -> io::Result<Self> {
+        let eventfd: RawFd = unsafe { libc::eventfd(0, libc::EFD_CLOEXEC | libc::EFD_NONBLOCK) };
+        if eventfd == -1 {
+            return Err(io::Error::last_os_error());
+        }
+        let inner: Inner = Inner {
+            eventfd,
+            parked: AtomicBool::new(false),
+            notified: AtomicBool::new(false),
+        };
+        Ok(Self { inner: Arc::new(inner) })
+    }
+
+    /// Returns an unparker for the target [Parker].
+    pub fn unparker(&self) -> Unparker {
+        Unparker {
+            inner: self.inner.clone(),
+        }
+    }
+
+    /// Blocks the current thread until it is unparked or `timeout` elapses, whatever happens first. If `timeout` is
+    /// `None`, this blocks until the thread is unparked. If the thread was unparked since it last parked, this returns
+    /// right away.
+    pub fn park(&self, timeout: Option<Duration>) -> io::Result<()> {
+        let inner: &Inner = &self.inner;
+        // Announce that we are about to park before checking for notifications, so that unparkers that come after the
+        // check see that they have to write to the eventfd.
+        inner.parked.store(true, Ordering::SeqCst);
+        if inner.notified.swap(false, Ordering::SeqCst) || timeout == Some(Duration::ZERO) {
+            inner.parked.store(false, Ordering::SeqCst);
+            return Ok(());
+        }
+
+        let timespec: Option<libc::timespec> = timeout.map(|timeout| libc::timespec {
+            tv_sec: timeout.as_secs().min(libc::time_t::MAX as u64) as libc::time_t,
+            tv_nsec: timeout.subsec_nanos() as libc::c_long,
+        });
+        let mut pollfd: libc::pollfd = libc::pollfd {
+            fd: inner.eventfd,
+            events: libc::POLLIN,
+            revents: 0,
+        };
+        let timespec_ptr: *const libc::timespec = timespec.as_ref().map_or(ptr::null(), |timespec| timespec);
+        let ret: libc::c_int = unsafe { libc::ppoll(&mut pollfd, 1, timespec_ptr, ptr::null()) };
+        let result: io::Result<()> = match ret {
+            -1 => match io::Error::last_os_error() {
+                e if e.kind() == io::ErrorKind::Interrupted => Ok(()),
+                e => Err(e),
+            },
+            _ => Ok(()),
+        };
+
+        // Reset the eventfd. It may fail with EAGAIN if we timed out, which is fine.
+        let mut counter: u64 = 0;
+        unsafe { libc::read(inner.eventfd, &mut counter as *mut u64 as *mut libc::c_void, 8) };
+        // Clear notifications before announcing that we are no longer parked, so that an unparker that comes in between
+        // writes to the eventfd, and thus is not lost.
+        inner.notified.store(false, Ordering::SeqCst);
+        inner.parked.store(false, Ordering::SeqCst);
+        result
+    }
+}
+
+/// Associate Functions for Unparkers
+impl Unparker {
+    /// Wakes up the thread that parks on the corresponding [Parker]. If the thread is not parked, its next park returns
+    /// right away.
+    pub fn unpark(&self) {
+        let inner: &Inner = &self.inner;
+        if inner.notified.swap(true, Ordering::SeqCst) {
+            // Somebody else already unparked the thread.
+            return;
+        }
+        if inner.parked.load(Ordering::SeqCst) {
+            let counter: u64 = 1;
+            unsafe { libc::write(inner.eventfd, &counter as *const u64 as *const libc::c_void, 8) };
+        }
+    }
+}
+
+//==============================================================================
+// Trait Implementations
+//==============================================================================
+
+/// Drop Trait Implementation for Parker State
+impl Drop for Inner {
+    fn drop(&mut self) {
+        unsafe { libc::close(self.eventfd) };
+    }
+}
+
+//==============================================================================
+// Unit Tests
+//==============================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        Parker,
+        Unparker,
+    };
+    use ::std::{
+        thread,
+        time::{
+            Duration,
+            Instant,
+        },
+    };
+
+    #[test]
+    fn park_timeout() {
+        let parker: Parker = Parker::new().expect("new() failed");
+        let start: Instant = Instant::now();
+        parker.park(Some(Duration::from_millis(5))).expect("park() failed");
+        assert!(start.elapsed() >= Duration::from_millis(5));
+    }
+
+    #[test]
+    fn unpark_before_park() {
+        let parker: Parker = Parker::new().expect("new() failed");
+        parker.unparker().unpark();
+        // Does not block.
+        parker.park(None).expect("park() failed");
+    }
+
+    #[test]
+    fn unpark_from_thread() {
+        let parker: Parker = Parker::new().expect("new() failed");
+        let unparker: Unparker = parker.unparker();
+        let thread: thread::JoinHandle<()> = thread::spawn(move || {
+            thread::sleep(Duration::from_millis(10));
+            unparker.unpark();
+        });
+        parker.park(None).expect("park() failed");
+        thread.join().expect("thread panicked");
+    }
+}
@@ -0,0 +1,745 @@
+// Copyright (c) Microsoft Corporation.
+// Licensed under the MIT license.
+
+//! Io_uring reactor.
+//!
+//! Operations are pushed into the submission queue when they are issued, but
+//! the queue is only handed over to the kernel once per iteration of the event
+//! loop, so that all operations issued in a poll round share a single system
+//! call. Completions are reaped right after, and wake up the tasks that issued
+//! the corresponding operations.
+//!
+//! Buffers are owned by the reactor while an operation is in flight. If the
+//! future of an operation is dropped before it completes, the buffer is kept
+//! alive until the kernel is done with it. Likewise, dropping the reactor
+//! cancels the operations in flight and waits for them to complete before
+//! their buffers are freed.
+
+//==============================================================================
+// Imports
+//==============================================================================
+
+use crate::{
+    consume_budget,
+    Scheduler,
+    SchedulerHandle,
+};
+use ::io_uring::{
+    opcode,
+    squeue,
+    types,
+    IoUring,
+};
+use ::std::{
+    cell::{
+        RefCell,
+        RefMut,
+    },
+    collections::HashMap,
+    future::Future,
+    io,
+    mem,
+    os::unix::io::RawFd,
+    pin::Pin,
+    rc::Rc,
+    task::{
+        Context,
+        Poll,
+        Waker,
+    },
+    time::{
+        Duration,
+        Instant,
+    },
+};
+
+//==============================================================================
+// Constants
+//==============================================================================
+
+/// Default number of entries in the submission queue.
+const DEFAULT_ENTRIES: u32 = 256;
+
+/// Flag that is set in the user data of timeouts that bound blocking waits.
+const TIMEOUT_FLAG: u64 = 1 << 63;
+
+/// User data of cancellations of operations and timeouts in flight.
+const CANCEL_ID: u64 = u64::MAX;
+
+//==============================================================================
+// Structures
+//==============================================================================
+
+/// In-flight Operation
+struct Operation {
+    /// Buffer that the kernel reads from or writes to.
+    buf: Vec<u8>,
+    /// Result of the operation, once it completes.
+    result: Option<i32>,
+    /// Waker of the task that issued the operation.
+    waker: Option<Waker>,
+    /// Whether or not the future of the operation was dropped.
+    orphaned: bool,
+}
+
+/// Reactor State
+struct Inner {
+    /// Underlying ring.
+    ring: IoUring,
+    /// Operations that are in flight, by user data.
+    operations: HashMap<u64, Operation>,
+    /// User data of the next operation.
+    next_id: u64,
+    /// Number of entries pushed into the submission queue since it was last handed over to the kernel.
+    unsubmitted: usize,
+    /// User data of the next timeout.
+    next_timeout_id: u64,
+    /// User data and deadline of the timeout that bounds blocking waits, if armed. Timeouts that were replaced are
+    /// cancelled, so no wait blocks on them.
+    armed: Option<(u64, Instant)>,
+    /// Duration of the last timeout. The kernel reads it when the timeout is submitted.
+    timespec: types::Timespec,
+}
+
+/// Io_uring Reactor
+#[derive(Clone)]
+pub struct UringReactor {
+    inner: Rc<RefCell<Inner>>,
+}
+
+/// Completion Future
+///
+/// This future completes once the kernel completes an operation.
+pub struct Completion<T> {
+    /// Reactor that drives this future.
+    reactor: UringReactor,
+    /// User data of the target operation.
+    id: u64,
+    /// Converts the result of the target operation into the output of this future.
+    map: fn(io::Result<u32>, Vec<u8>) -> T,
+    /// Whether or not this future has completed.
+    done: bool,
+}
+
+//==============================================================================
+// Associate Functions
+//==============================================================================
+
+/// Associate Functions for Reactor State
+impl Inner {
+    /// Pushes `entry` into the submission queue. If the queue is full, it is handed over to the kernel first.
+    unsafe fn push(&mut self, entry: &squeue::Entry) -> io::Result<()> {
+        if self.ring.submission().is_full() {
+            self.ring.submit()?;
+            self.unsubmitted = 0;
+        }
+        self.ring
+            .submission()
+            .push(entry)
+            .map_err(|_| io::Error::from(io::ErrorKind::WouldBlock))?;
+        self.unsubmitted += 1;
+        Ok(())
+    }
+
+    /// Arms a timeout that expires at `deadline`, unless an earlier one is already armed. A later one is cancelled.
+    fn arm_timeout(&mut self, now: Instant, deadline: Instant) -> io::Result<()> {
+        if matches!(self.armed, Some((_, armed)) if armed <= deadline) {
+            return Ok(());
+        }
+        self.disarm_timeout()?;
+        let timeout: Duration = deadline.saturating_duration_since(now);
+        self.timespec = types::Timespec::new()
+            .sec(timeout.as_secs())
+            .nsec(timeout.subsec_nanos());
+        let id: u64 = TIMEOUT_FLAG | self.next_timeout_id;
+        self.next_timeout_id += 1;
+        let entry: squeue::Entry = opcode::Timeout::new(&self.timespec).build().user_data(id);
+        unsafe { self.push(&entry)? };
+        self.armed = Some((id, deadline));
+        Ok(())
+    }
+
+    /// Cancels the armed timeout, if any.
+    fn disarm_timeout(&mut self) -> io::Result<()> {
+        if let Some((id, _)) = self.armed.take() {
+            let entry: squeue::Entry = opcode::TimeoutRemove::new(id).build().user_data(CANCEL_ID);
+            unsafe { self.push(&entry)? };
+        }
+        Ok(())
+    }
+}
+
+/// Associate Functions for Io_uring Reactors
+impl UringReactor {
+    /// Creates an io_uring reactor with the default number of submission queue entries.
+    pub fn new() -> io::Result<Self> {
+        Self::with_entries(DEFAULT_ENTRIES)
+    }
+
+    /// Creates an io_uring reactor with `entries` submission queue entries.
+    pub fn with_entries(entries: u32) -> io::Result<Self> {
+        let inner: Inner = Inner {
+            ring: IoUring::new(entries)?,
+            operations: HashMap::new(),
+            next_id: 0,
+            unsubmitted: 0,
+            next_timeout_id: 0,
+            armed: None,
+            timespec: types::Timespec::new(),
+        };
+        Ok(Self {
+            inner: Rc::new(RefCell::new(inner)),
+        })
+    }
+
+    /// Issues a read of `len` bytes from `fd`, at `offset`. Non-seekable file descriptors require `offset` to be
+    /// zero. The future returns the number of bytes read, along with the buffer that holds them.
+    /// Reads of more than [u32::MAX] bytes fail with [io::ErrorKind::InvalidInput].
+    pub fn read(&self, fd: RawFd, len: usize, offset: u64) -> Completion<(io::Result<usize>, Vec<u8>)> {
+        let len32: u32 = match u32::try_from(len) {
+            Ok(len32) => len32,
+            Err(_) => return self.reject(libc::EINVAL, Vec::new(), map_read),
+        };
+        let mut buf: Vec<u8> = vec![0; len];
+        let entry: squeue::Entry = opcode::Read::new(types::Fd(fd), buf.as_mut_ptr(), len32)
+            .offset(offset as i64)
+            .build();
+        self.issue(entry, buf, map_read)
+    }
+
+    /// Issues a write of `buf` to `fd`, at `offset`. Non-seekable file descriptors require `offset` to be zero. The
+    /// future returns the number of bytes written, along with the buffer. Writes of more than [u32::MAX] bytes fail
+    /// with [io::ErrorKind::InvalidInput].
+    pub fn write(&self, fd: RawFd, buf: Vec<u8>, offset: u64) -> Completion<(io::Result<usize>, Vec<u8>)> {
+        let len32: u32 = match u32::try_from(buf.len()) {
+            Ok(len32) => len32,
+            Err(_) => return self.reject(libc::EINVAL, buf, map_write),
+        };
+        let entry: squeue::Entry = opcode::Write::new(types::Fd(fd), buf.as_ptr(), len32)
+            .offset(offset as i64)
+            .build();
+        self.issue(entry, buf, map_write)
+    }
+
+    /// Issues a synchronization of `fd` to storage.
+    pub fn fsync(&self, fd: RawFd) -> Completion<io::Result<()>> {
+        let entry: squeue::Entry = opcode::Fsync::new(types::Fd(fd)).build();
+        self.issue(entry, Vec::new(), |result, _| result.map(|_| ()))
+    }
+
+    /// Issues an operation that does nothing.
+    pub fn nop(&self) -> Completion<io::Result<()>> {
+        let entry: squeue::Entry = opcode::Nop::new().build();
+        self.issue(entry, Vec::new(), |result, _| result.map(|_| ()))
+    }
+
+    /// Returns the number of operations in flight in the target [UringReactor].
+    pub fn len(&self) -> usize {
+        self.inner.borrow().operations.len()
+    }
+
+    /// Queries whether or not no operation is in flight in the target [UringReactor].
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Hands pending submissions over to the kernel, and reaps completions. If `wait` is set and some operation is
+    /// in flight, this blocks until at least one completion arrives. The number of reaped completions is returned.
+    pub fn submit_and_reap(&self, wait: bool) -> io::Result<usize> {
+        {
+            let mut inner: RefMut<Inner> = self.inner.borrow_mut();
+            let want: usize = if wait && (!inner.operations.is_empty() || inner.armed.is_some()) {
+                1
+            } else {
+                0
+            };
+            if want > 0 || inner.unsubmitted > 0 {
+                match inner.ring.submit_and_wait(want) {
+                    Ok(_) => (),
+                    Err(e) if e.kind() == io::ErrorKind::Interrupted => (),
+                    Err(e) => return Err(e),
+                }
+                inner.unsubmitted = 0;
+            }
+        }
+        Ok(self.reap())
+    }
+
+    /// Runs one iteration of the event loop: polls `scheduler`, hands submissions issued in that poll round over to
+    /// the kernel, and reaps completions. If no task is notified after polling, this blocks until some operation
+    /// completes or the next timer of `scheduler` expires.
+    pub fn turn(&self, scheduler: &Scheduler) -> io::Result<()> {
+        scheduler.poll();
+        self.park(scheduler)
+    }
+
+    /// Runs the event loop until the task of `handle` completes.
+    pub fn run_until(&self, scheduler: &Scheduler, handle: &SchedulerHandle) -> io::Result<()> {
+        loop {
+            scheduler.poll();
+            if handle.has_completed() {
+                return Ok(());
+            }
+            self.park(scheduler)?;
+        }
+    }
+
+    /// Submits and reaps, blocking only if no task in `scheduler` is notified. Blocking is bounded by the next timer
+    /// of `scheduler`. If there is no timer, the armed timeout is cancelled, since nobody waits on it anymore.
+    fn park(&self, scheduler: &Scheduler) -> io::Result<()> {
+        let wait: bool = !scheduler.has_notified();
+        if wait {
+            let mut inner: RefMut<Inner> = self.inner.borrow_mut();
+            match scheduler.timer().next_deadline() {
+                Some(deadline) => inner.arm_timeout(scheduler.now(), deadline)?,
+                None => inner.disarm_timeout()?,
+            }
+        }
+        self.submit_and_reap(wait)?;
+        Ok(())
+    }
+
+    /// Pushes `entry` into the submission queue, handing `buf` over to the target [UringReactor] until the operation
+    /// completes.
+    fn issue<T>(&self, entry: squeue::Entry, buf: Vec<u8>, map: fn(io::Result<u32>, Vec<u8>) -> T) -> Completion<T> {
+        let mut inner: RefMut<Inner> = self.inner.borrow_mut();
+        let id: u64 = inner.next_id;
+        inner.next_id += 1;
+        let mut operation: Operation = Operation {
+            buf,
+            result: None,
+            waker: None,
+            orphaned: false,
+        };
+        // Safety: the buffer is owned by the reactor until the operation completes.
+        if let Err(e) = unsafe { inner.push(&entry.user_data(id)) } {
+            operation.result = Some(-e.raw_os_error().unwrap_or(libc::EIO));
+        }
+        inner.operations.insert(id, operation);
+        Completion {
+            reactor: self.clone(),
+            id,
+            map,
+            done: false,
+        }
+    }
+
+    /// Returns a future that fails right away with `errno`, for an operation that cannot be issued.
+    fn reject<T>(&self, errno: i32, buf: Vec<u8>, map: fn(io::Result<u32>, Vec<u8>) -> T) -> Completion<T> {
+        let mut inner: RefMut<Inner> = self.inner.borrow_mut();
+        let id: u64 = inner.next_id;
+        inner.next_id += 1;
+        let operation: Operation = Operation {
+            buf,
+            result: Some(-errno),
+            waker: None,
+            orphaned: false,
+        };
+        inner.operations.insert(id, operation);
+        Completion {
+            reactor: self.clone(),
+            id,
+            map,
+            done: false,
+        }
+    }
+
+    /// Reaps completions, waking up the tasks that issued the corresponding operations.
+    fn reap(&self) -> usize {
+        let mut wakers: Vec<Waker> = Vec::new();
+        let nreaped: usize = {
+            let mut inner: RefMut<Inner> = self.inner.borrow_mut();
+            let inner: &mut Inner = &mut inner;
+            let mut nreaped: usize = 0;
+            for entry in inner.ring.completion() {
+                nreaped += 1;
+                if entry.user_data() == CANCEL_ID {
+                    continue;
+                }
+                if entry.user_data() & TIMEOUT_FLAG != 0 {
+                    if matches!(inner.armed, Some((id, _)) if id == entry.user_data()) {
+                        inner.armed = None;
+                    }
+                    continue;
+                }
+                let operation: &mut Operation = match inner.operations.get_mut(&entry.user_data()) {
+                    Some(operation) => operation,
+                    None => continue,
+                };
+                if operation.orphaned {
+                    inner.operations.remove(&entry.user_data());
+                    continue;
+                }
+                operation.result = Some(entry.result());
+                wakers.extend(operation.waker.take());
+            }
+            nreaped
+        };
+
+        // Wake up tasks outside of the borrow, since wakers may call back into the reactor.
+        wakers.into_iter().for_each(Waker::wake);
+        nreaped
+    }
+}
+
+//==============================================================================
+// Trait Implementations
+//==============================================================================
+
+/// Future Trait Implementation for Completion Futures
+impl<T> Future for Completion<T> {
+    type Output = T;
+
+    /// Polls the target [Completion].
+    fn poll(self: Pin<&mut Self>, ctx: &mut Context) -> Poll<T> {
+        // Operations that complete right away should not monopolize the poll pass.
+        if consume_budget(ctx).is_pending() {
+            return Poll::Pending;
+        }
+        let self_: &mut Completion<T> = self.get_mut();
+        assert!(!self_.done, "polled after completion");
+        let mut inner: RefMut<Inner> = self_.reactor.inner.borrow_mut();
+        let operation: &mut Operation = inner.operations.get_mut(&self_.id).expect("unknown operation");
+        match operation.result {
+            Some(result) => {
+                let operation: Operation = inner.operations.remove(&self_.id).expect("unknown operation");
+                self_.done = true;
+                let result: io::Result<u32> = if result < 0 {
+                    Err(io::Error::from_raw_os_error(-result))
+                } else {
+                    Ok(result as u32)
+                };
+                Poll::Ready((self_.map)(result, operation.buf))
+            },
+            None => {
+                match &operation.waker {
+                    Some(waker) if waker.will_wake(ctx.waker()) => (),
+                    _ => operation.waker = Some(ctx.waker().clone()),
+                }
+                Poll::Pending
+            },
+        }
+    }
+}
+
+/// Drop Trait Implementation for Completion Futures
+impl<T> Drop for Completion<T> {
+    fn drop(&mut self) {
+        if self.done {
+            return;
+        }
+        let mut inner: RefMut<Inner> = self.reactor.inner.borrow_mut();
+        let completed: bool = match inner.operations.get_mut(&self.id) {
+            Some(operation) if operation.result.is_none() => {
+                // Keep the buffer alive until the kernel is done with it.
+                operation.orphaned = true;
+                operation.waker = None;
+                false
+            },
+            _ => true,
+        };
+        if completed {
+            inner.operations.remove(&self.id);
+        }
+    }
+}
+
+/// Drop Trait Implementation for Reactor State
+impl Drop for Inner {
+    /// Cancels the operations in flight, and waits for the kernel to be done with their buffers before the ring is
+    /// closed and the buffers are freed.
+    fn drop(&mut self) {
+        let ids: Vec<u64> = self
+            .operations
+            .iter()
+            .filter(|(_, operation)| operation.result.is_none())
+            .map(|(id, _)| *id)
+            .collect();
+        let mut in_flight: usize = ids.len();
+        for id in ids {
+            let entry: squeue::Entry = opcode::AsyncCancel::new(id).build().user_data(CANCEL_ID);
+            if unsafe { self.push(&entry) }.is_err() {
+                // The kernel may still write into the buffers, so leak them rather than free them.
+                mem::forget(mem::take(&mut self.operations));
+                return;
+            }
+        }
+
+        while in_flight > 0 {
+            match self.ring.submit_and_wait(1) {
+                Ok(_) => (),
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => (),
+                Err(_) => {
+                    mem::forget(mem::take(&mut self.operations));
+                    return;
+                },
+            }
+            for entry in self.ring.completion() {
+                if let Some(operation) = self.operations.get_mut(&entry.user_data()) {
+                    if operation.result.is_none() {
+                        operation.result = Some(entry.result());
+                        in_flight -= 1;
+                    }
+                }
+            }
+        }
+    }
+}
+
+//==============================================================================
+// Standalone Functions
+//==============================================================================
+
+/// Converts the result of a read into the output of its future, trimming the buffer to the bytes that were read.
+fn map_read(result: io::Result<u32>, mut buf: Vec<u8>) -> (io::Result<usize>, Vec<u8>) {
+    let result: io::Result<usize> = result.map(|n| n as usize);
+    if let Ok(n) = result {
+        buf.truncate(n);
+    }
+    (result, buf)
+}
+
+/// Converts the result of a write into the output of its future.
+fn map_write(result: io::Result<u32>, buf: Vec<u8>) -> (io::Result<usize>, Vec<u8>) {
+    (result.map(|n| n as usize), buf)
+}
+
+//==============================================================================
+// Unit Tests
+//==============================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        Completion,
+        UringReactor,
+    };
+    use crate::{
+        test_utils::TestTask,
+        Scheduler,
+        SchedulerHandle,
+        Timer,
+    };
+    use ::std::{
+        cell::RefCell,
+        fs::File,
+        io::{
+            self,
+            Write,
+        },
+        os::unix::io::{
+            AsRawFd,
+            RawFd,
+        },
+        path::PathBuf,
+        rc::Rc,
+        time::{
+            Duration,
+            Instant,
+        },
+    };
+
+    fn pipe() -> (RawFd, RawFd) {
+        let mut fds: [RawFd; 2] = [0; 2];
+        assert_eq!(unsafe { libc::pipe2(fds.as_mut_ptr(), libc::O_CLOEXEC) }, 0);
+        (fds[0], fds[1])
+    }
+
+    fn temp_path(name: &str) -> PathBuf {
+        ::std::env::temp_dir().join(format!("scheduler-uring-{}-{}", name, ::std::process::id()))
+    }
+
+    #[test]
+    fn read_file() {
+        let path: PathBuf = temp_path("read");
+        File::create(&path)
+            .and_then(|mut file| file.write_all(b"hello, world"))
+            .expect("failed to create file");
+        let file: File = File::open(&path).expect("failed to open file");
+        let fd: RawFd = file.as_raw_fd();
+
+        let scheduler: Scheduler = Scheduler::default();
+        let reactor: UringReactor = UringReactor::new().expect("new() failed");
+        let data: Rc<RefCell<Vec<u8>>> = Rc::new(RefCell::new(Vec::new()));
+        let data_: Rc<RefCell<Vec<u8>>> = data.clone();
+        let reactor_: UringReactor = reactor.clone();
+        let handle: SchedulerHandle = scheduler
+            .insert(TestTask::new(async move {
+                let (result, buf): (io::Result<usize>, Vec<u8>) = reactor_.read(fd, 5, 7).await;
+                assert_eq!(result.expect("read() failed"), 5);
+                *data_.borrow_mut() = buf;
+            }))
+            .expect("insert() failed");
+
+        reactor.run_until(&scheduler, &handle).expect("run_until() failed");
+        assert_eq!(&*data.borrow(), b"world");
+        assert!(reactor.is_empty());
+        drop(file);
+        ::std::fs::remove_file(&path).expect("failed to remove file");
+    }
+
+    #[test]
+    fn pipe_round_trip() {
+        let scheduler: Scheduler = Scheduler::default();
+        let reactor: UringReactor = UringReactor::new().expect("new() failed");
+        let (rx, tx): (RawFd, RawFd) = pipe();
+
+        let data: Rc<RefCell<Vec<u8>>> = Rc::new(RefCell::new(Vec::new()));
+        let data_: Rc<RefCell<Vec<u8>>> = data.clone();
+        let reactor_: UringReactor = reactor.clone();
+        let reader: SchedulerHandle = scheduler
+            .insert(TestTask::new(async move {
+                let (result, buf): (io::Result<usize>, Vec<u8>) = reactor_.read(rx, 16, 0).await;
+                assert_eq!(result.expect("read() failed"), 4);
+                *data_.borrow_mut() = buf;
+            }))
+            .expect("insert() failed");
+
+        // The read blocks in the kernel until the writer shows up.
+        scheduler.poll();
+        reactor.submit_and_reap(false).expect("submit_and_reap() failed");
+        assert_eq!(reactor.len(), 1);
+
+        let reactor_: UringReactor = reactor.clone();
+        let _writer: SchedulerHandle = scheduler
+            .insert(TestTask::new(async move {
+                let (result, _): (io::Result<usize>, Vec<u8>) = reactor_.write(tx, b"ping".to_vec(), 0).await;
+                assert_eq!(result.expect("write() failed"), 4);
+            }))
+            .expect("insert() failed");
+
+        reactor.run_until(&scheduler, &reader).expect("run_until() failed");
+        assert_eq!(&*data.borrow(), b"ping");
+        unsafe {
+            libc::close(rx);
+            libc::close(tx);
+        }
+    }
+
+    #[test]
+    fn submissions_are_batched() {
+        let scheduler: Scheduler = Scheduler::default();
+        let reactor: UringReactor = UringReactor::new().expect("new() failed");
+        let mut handles: Vec<SchedulerHandle> = Vec::new();
+        for _ in 0..8 {
+            let reactor_: UringReactor = reactor.clone();
+            handles.push(
+                scheduler
+                    .insert(TestTask::new(async move {
+                        reactor_.nop().await.expect("nop() failed");
+                    }))
+                    .expect("insert() failed"),
+            );
+        }
+
+        scheduler.poll();
+        assert_eq!(reactor.inner.borrow().unsubmitted, 8);
+        assert!(reactor.submit_and_reap(true).expect("submit_and_reap() failed") > 0);
+        assert_eq!(reactor.inner.borrow().unsubmitted, 0);
+
+        while !handles.iter().all(|handle| handle.has_completed()) {
+            reactor.turn(&scheduler).expect("turn() failed");
+        }
+        assert!(reactor.is_empty());
+    }
+
+    #[test]
+    fn stale_timeout_does_not_block() {
+        let scheduler: Scheduler = Scheduler::default();
+        let reactor: UringReactor = UringReactor::new().expect("new() failed");
+        let timer: Timer = scheduler.timer();
+        let reactor_: UringReactor = reactor.clone();
+        let handle: SchedulerHandle = scheduler
+            .insert(TestTask::new(async move {
+                let nop: Completion<io::Result<()>> = reactor_.nop();
+                let result: io::Result<()> = timer.timeout(nop, Duration::from_secs(60)).await.expect("timed out");
+                result.expect("nop() failed");
+            }))
+            .expect("insert() failed");
+
+        // A timeout is armed for the timer of the task, which goes away once the task completes.
+        let start: Instant = Instant::now();
+        reactor.turn(&scheduler).expect("turn() failed");
+        assert!(reactor.inner.borrow().armed.is_some());
+        reactor.turn(&scheduler).expect("turn() failed");
+        assert!(handle.has_completed());
+        assert!(reactor.inner.borrow().armed.is_none());
+        reactor.turn(&scheduler).expect("turn() failed");
+        assert!(start.elapsed() < Duration::from_secs(60));
+    }
+
+    #[test]
+    fn oversized_buffer() {
+        let scheduler: Scheduler = Scheduler::default();
+        let reactor: UringReactor = UringReactor::new().expect("new() failed");
+        let reactor_: UringReactor = reactor.clone();
+        let handle: SchedulerHandle = scheduler
+            .insert(TestTask::new(async move {
+                let (result, _): (io::Result<usize>, Vec<u8>) = reactor_.read(0, u32::MAX as usize + 1, 0).await;
+                assert_eq!(
+                    result.expect_err("read() succeeded").kind(),
+                    io::ErrorKind::InvalidInput
+                );
+            }))
+            .expect("insert() failed");
+
+        reactor.run_until(&scheduler, &handle).expect("run_until() failed");
+        assert!(reactor.is_empty());
+    }
+
+    #[test]
+    fn dropped_operation_keeps_buffer() {
+        let scheduler: Scheduler = Scheduler::default();
+        let reactor: UringReactor = UringReactor::new().expect("new() failed");
+        let (rx, tx): (RawFd, RawFd) = pipe();
+
+        let timer: Timer = scheduler.timer();
+        let reactor_: UringReactor = reactor.clone();
+        let handle: SchedulerHandle = scheduler
+            .insert(TestTask::new(async move {
+                let read: Completion<(io::Result<usize>, Vec<u8>)> = reactor_.read(rx, 16, 0);
+                assert!(timer.timeout(read, Duration::from_millis(5)).await.is_err());
+            }))
+            .expect("insert() failed");
+
+        let start: Instant = Instant::now();
+        reactor.run_until(&scheduler, &handle).expect("run_until() failed");
+        assert!(start.elapsed() >= Duration::from_millis(5));
+        assert_eq!(reactor.len(), 1);
+
+        // Once the read completes, the orphaned operation is released.
+        assert_eq!(unsafe { libc::write(tx, b"x".as_ptr() as *const libc::c_void, 1) }, 1);
+        while !reactor.is_empty() {
+            reactor.submit_and_reap(true).expect("submit_and_reap() failed");
+        }
+        unsafe {
+            libc::close(rx);
+            libc::close(tx);
+        }
+    }
+
+    #[test]
+    fn drop_cancels_operations() {
+        let scheduler: Scheduler = Scheduler::default();
+        let reactor: UringReactor = UringReactor::new().expect("new() failed");
+        let (rx, tx): (RawFd, RawFd) = pipe();
+
+        let reactor_: UringReactor = reactor.clone();
+        let _handle: SchedulerHandle = scheduler
+            .insert(TestTask::new(async move {
+                let _ = reactor_.read(rx, 16, 0).await;
+            }))
+            .expect("insert() failed");
+        scheduler.poll();
+        reactor.submit_and_reap(false).expect("submit_and_reap() failed");
+        assert_eq!(reactor.len(), 1);
+
+        // Dropping the reactor waits for the read to be cancelled, although nothing is ever written to the pipe.
+        drop(scheduler);
+        drop(reactor);
+        unsafe {
+            libc::close(rx);
+            libc::close(tx);
+        }
+    }
+}
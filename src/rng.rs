@@ -0,0 +1,104 @@
+// Copyright (c) Microsoft Corporation.
+// Licensed under the MIT license.
+
+//! A small, seedable pseudo-random number generator.
+//!
+//! This is an implementation of SplitMix64. We rely on our own generator,
+//! rather than an external crate, so that a given seed always produces the
+//! same sequence of numbers across releases.
+
+//==============================================================================
+// Structures
+//==============================================================================
+
+/// Pseudo-Random Number Generator
+pub struct Rng {
+    /// Current state of the generator.
+    state: u64,
+}
+
+//==============================================================================
+// Associate Functions
+//==============================================================================
+
+/// Associate Functions for Pseudo-Random Number Generators
+impl Rng {
+    /// Creates a generator that is initialized with `seed`.
+    pub fn new(seed: u64) -> Self {
+        Self { state: seed }
+    }
+
+    /// Returns the next number in the sequence of the target [Rng].
+    pub fn next_u64(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z: u64 = self.state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+
+    /// Returns a number in the range `[0, n)`.
+    pub fn gen_range(&mut self, n: usize) -> usize {
+        debug_assert!(n > 0);
+        ((self.next_u64() as u128 * n as u128) >> 64) as usize
+    }
+
+    /// Returns `true` with probability `p`.
+    pub fn gen_bool(&mut self, p: f64) -> bool {
+        // Use the 53 most significant bits, which is the precision of a double.
+        ((self.next_u64() >> 11) as f64) / ((1u64 << 53) as f64) < p
+    }
+
+    /// Shuffles `items` in place.
+    pub fn shuffle<T>(&mut self, items: &mut [T]) {
+        for i in (1..items.len()).rev() {
+            let j: usize = self.gen_range(i + 1);
+            items.swap(i, j);
+        }
+    }
+}
+
+//==============================================================================
+// Unit Tests
+//==============================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::Rng;
+
+    #[test]
+    fn rng_is_deterministic() {
+        let mut a: Rng = Rng::new(42);
+        let mut b: Rng = Rng::new(42);
+        for _ in 0..1024 {
+            assert_eq!(a.next_u64(), b.next_u64());
+        }
+    }
+
+    #[test]
+    fn rng_shuffle() {
+        let mut rng: Rng = Rng::new(7);
+        let mut items: Vec<usize> = (0..64).collect();
+        rng.shuffle(&mut items);
+        assert_ne!(items, (0..64).collect::<Vec<usize>>());
+        items.sort();
+        assert_eq!(items, (0..64).collect::<Vec<usize>>());
+    }
+
+    #[test]
+    fn rng_gen_bool() {
+        let mut rng: Rng = Rng::new(0);
+        for _ in 0..1024 {
+            assert!(rng.gen_bool(1.0));
+            assert!(!rng.gen_bool(0.0));
+        }
+    }
+
+    #[test]
+    fn rng_gen_range() {
+        let mut rng: Rng = Rng::new(0);
+        for n in 1..1024 {
+            assert!(rng.gen_range(n) < n);
+        }
+    }
+}
@@ -0,0 +1,136 @@
+// Copyright (c) Microsoft Corporation.
+// Licensed under the MIT license.
+
+//! Chaos mode for the [crate::Scheduler].
+//!
+//! In chaos mode the scheduler perturbs wakeups: it injects spurious wakeups
+//! on idle tasks and holds back real wakeups for some poll rounds. Futures
+//! that honor the contract of [std::future::Future] are robust to both.
+
+//==============================================================================
+// Imports
+//==============================================================================
+
+use crate::{
+    rng::Rng,
+    scheduler::simulation::SeedReporter,
+};
+use ::bit_iter::BitIter;
+
+//==============================================================================
+// Structures
+//==============================================================================
+
+/// Chaos Configuration
+#[derive(Clone, Copy, Debug)]
+pub struct ChaosConfig {
+    /// Seed for the pseudo-random number generator that drives chaos.
+    pub seed: u64,
+    /// Probability of waking up an idle task in a poll round.
+    pub spurious_wakeup_probability: f64,
+    /// Probability of delaying a wakeup.
+    pub delay_probability: f64,
+    /// Maximum number of poll rounds that a wakeup is delayed by.
+    pub max_delay_rounds: u64,
+}
+
+/// Chaos State
+pub struct Chaos {
+    /// Configuration.
+    config: ChaosConfig,
+    /// Drives chaos.
+    rng: Rng,
+    /// Delayed wakeups of each page, as pairs of poll round at which they are released and index in the page.
+    delayed: Vec<Vec<(u64, usize)>>,
+    /// Reports the seed if the run fails.
+    reporter: SeedReporter,
+}
+
+//==============================================================================
+// Associate Functions
+//==============================================================================
+
+/// Associate Functions for Chaos
+impl Chaos {
+    /// Creates a chaos state from `config`.
+    pub fn new(config: ChaosConfig) -> Self {
+        Self {
+            config,
+            rng: Rng::new(config.seed),
+            delayed: Vec::new(),
+            reporter: SeedReporter::new("chaos", config.seed),
+        }
+    }
+
+    /// Returns the configuration of the target [Chaos].
+    pub fn config(&self) -> &ChaosConfig {
+        &self.config
+    }
+
+    /// Returns a guard that reports the seed of the target [Chaos] if a panic unwinds through it.
+    pub fn reporter(&self) -> SeedReporter {
+        self.reporter.clone()
+    }
+
+    /// Queries whether or not some wakeup is held back by the target [Chaos].
+    pub fn has_delayed(&self) -> bool {
+        self.delayed.iter().any(|delayed| !delayed.is_empty())
+    }
+
+    /// Perturbs the `notified` flags of the `page_ix` page in the `round`th poll round. The `live` flags tell which
+    /// tasks of the page may be polled. The flags of tasks that should be polled in this round are returned.
+    pub fn perturb(&mut self, round: u64, page_ix: usize, notified: u64, live: u64) -> u64 {
+        if self.delayed.len() <= page_ix {
+            self.delayed.resize_with(page_ix + 1, Vec::new);
+        }
+
+        // Hold back real wakeups.
+        let mut perturbed: u64 = notified;
+        if self.config.max_delay_rounds > 0 {
+            for subpage_ix in BitIter::from(notified) {
+                if self.rng.gen_bool(self.config.delay_probability) {
+                    let delay: u64 = 1 + self.rng.gen_range(self.config.max_delay_rounds as usize) as u64;
+                    self.delayed[page_ix].push((round + delay, subpage_ix));
+                    perturbed &= !(1 << subpage_ix);
+                }
+            }
+        }
+
+        // Release wakeups that were held back for long enough.
+        let mut released: u64 = 0;
+        self.delayed[page_ix].retain(|(at, subpage_ix)| {
+            if *at > round {
+                return true;
+            }
+            released |= 1 << subpage_ix;
+            false
+        });
+        perturbed |= released;
+
+        // Inject spurious wakeups.
+        for subpage_ix in BitIter::from(live & !perturbed) {
+            if self.rng.gen_bool(self.config.spurious_wakeup_probability) {
+                perturbed |= 1 << subpage_ix;
+            }
+        }
+
+        perturbed & live
+    }
+}
+
+//==============================================================================
+// Trait Implementations
+//==============================================================================
+
+/// Default Trait Implementation for Chaos Configurations
+impl Default for ChaosConfig {
+    /// Creates a configuration that does not perturb wakeups.
+    fn default() -> Self {
+        Self {
+            seed: 0,
+            spurious_wakeup_probability: 0.0,
+            delay_probability: 0.0,
+            max_delay_rounds: 0,
+        }
+    }
+}
@@ -0,0 +1,151 @@
+// Copyright (c) Microsoft Corporation.
+// Licensed under the MIT license.
+
+//! Cooperative preemption for the [crate::Scheduler].
+//!
+//! While a task is polled, it runs with a budget of operations, which is set
+//! through [crate::Scheduler::set_coop_budget]. Leaf futures that may always
+//! be ready, such as readiness of a busy socket, I/O completions, sleeps and
+//! joins, consume budget before making progress. Once the budget is
+//! exhausted, they return pending and wake up the task, which thus yields to
+//! the other tasks of the poll pass.
+
+//==============================================================================
+// Imports
+//==============================================================================
+
+use ::std::{
+    cell::Cell,
+    task::{
+        Context,
+        Poll,
+    },
+};
+
+//==============================================================================
+// Structures
+//==============================================================================
+
+/// Restores the budget that was set before a poll when dropped, so that it is restored even if the poll panics.
+struct RestoreBudget(Option<u32>);
+
+//==============================================================================
+// Thread Local Variables
+//==============================================================================
+
+thread_local! {
+    /// Budget left for the task that is being polled, if it is constrained.
+    static BUDGET: Cell<Option<u32>> = Cell::new(None);
+}
+
+//==============================================================================
+// Standalone Functions
+//==============================================================================
+
+/// Consumes one unit of the budget of the running task. If the budget is exhausted, the task is woken up and pending is
+/// returned, in which case the caller should return pending as well. Outside of a constrained task, this always returns
+/// ready.
+pub fn consume_budget(ctx: &mut Context) -> Poll<()> {
+    BUDGET.with(|budget: &Cell<Option<u32>>| match budget.get() {
+        None => Poll::Ready(()),
+        Some(0) => {
+            ctx.waker().wake_by_ref();
+            Poll::Pending
+        },
+        Some(left) => {
+            budget.set(Some(left - 1));
+            Poll::Ready(())
+        },
+    })
+}
+
+/// Queries whether or not the running task has some budget left.
+pub fn has_budget_remaining() -> bool {
+    BUDGET.with(|budget: &Cell<Option<u32>>| budget.get() != Some(0))
+}
+
+/// Runs `f` with `budget`, restoring the previous budget afterwards.
+pub fn with_budget<R>(budget: Option<u32>, f: impl FnOnce() -> R) -> R {
+    let _restore: RestoreBudget = RestoreBudget(BUDGET.with(|cell: &Cell<Option<u32>>| cell.replace(budget)));
+    f()
+}
+
+//==============================================================================
+// Trait Implementations
+//==============================================================================
+
+/// Drop Trait Implementation for Budget Restorers
+impl Drop for RestoreBudget {
+    fn drop(&mut self) {
+        BUDGET.with(|cell: &Cell<Option<u32>>| cell.set(self.0));
+    }
+}
+
+//==============================================================================
+// Unit Tests
+//==============================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        consume_budget,
+        has_budget_remaining,
+        with_budget,
+    };
+    use crate::{
+        test_utils::TestTask,
+        Scheduler,
+        SchedulerHandle,
+    };
+    use ::std::{
+        cell::Cell,
+        future::Future,
+        panic,
+        pin::Pin,
+        rc::Rc,
+        task::{
+            Context,
+            Poll,
+        },
+    };
+
+    /// Stream that always has an item, and consumes budget for each of them.
+    struct Busy;
+
+    impl Future for Busy {
+        type Output = ();
+
+        fn poll(self: Pin<&mut Self>, ctx: &mut Context) -> Poll<()> {
+            consume_budget(ctx)
+        }
+    }
+
+    #[test]
+    fn coop_budget() {
+        let scheduler: Scheduler = Scheduler::default();
+        scheduler.set_coop_budget(Some(4));
+        let items: Rc<Cell<usize>> = Rc::new(Cell::new(0));
+        let items_: Rc<Cell<usize>> = items.clone();
+        let _sender: SchedulerHandle = scheduler
+            .insert(TestTask::new(async move {
+                loop {
+                    Busy.await;
+                    items_.set(items_.get() + 1);
+                }
+            }))
+            .expect("insert() failed");
+
+        // The task yields once its budget is exhausted, and is polled again in the next pass.
+        scheduler.poll();
+        assert_eq!(items.get(), 4);
+        assert!(scheduler.has_notified());
+        scheduler.poll();
+        assert_eq!(items.get(), 8);
+    }
+
+    #[test]
+    fn coop_budget_restored_on_panic() {
+        assert!(panic::catch_unwind(|| with_budget(Some(0), || panic!("poll panicked"))).is_err());
+        assert!(has_budget_remaining());
+    }
+}
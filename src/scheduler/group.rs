@@ -0,0 +1,367 @@
+// Copyright (c) Microsoft Corporation.
+// Licensed under the MIT license.
+
+//! Task groups for the [crate::Scheduler].
+//!
+//! A [TaskGroup] owns the handles of its members. Members may be cancelled
+//! together, waited on together, and the group tells when all of them are
+//! done. Dropping a group cancels the members that are still running.
+
+//==============================================================================
+// Imports
+//==============================================================================
+
+use crate::{
+    Scheduler,
+    SchedulerFuture,
+    SchedulerHandle,
+};
+use ::std::{
+    any::Any,
+    cell::{
+        RefCell,
+        RefMut,
+    },
+    future::Future,
+    mem,
+    pin::Pin,
+    rc::Rc,
+    task::{
+        Context,
+        Poll,
+        Waker,
+    },
+};
+
+//==============================================================================
+// Structures
+//==============================================================================
+
+/// State Shared by a Group and its Members
+#[derive(Default)]
+struct State {
+    /// Number of members that have neither completed nor been cancelled.
+    running: usize,
+    /// Whether or not the group was cancelled.
+    cancelled: bool,
+    /// Wakers of tasks waiting for all members to be done.
+    waiters: Vec<Waker>,
+}
+
+/// Membership of a Task in a Group
+///
+/// When dropped, the member is accounted as done.
+struct Membership {
+    state: Rc<RefCell<State>>,
+}
+
+/// Group Member
+///
+/// This structure wraps the future of a task that belongs to a [TaskGroup].
+struct GroupMember<F: SchedulerFuture> {
+    /// Underlying future.
+    future: F,
+    /// Membership in the group, until the future completes.
+    membership: Option<Membership>,
+}
+
+/// Task Group
+pub struct TaskGroup {
+    /// Scheduler that runs the members.
+    scheduler: Scheduler,
+    /// Handles of the members.
+    handles: Vec<SchedulerHandle>,
+    /// State shared with the members.
+    state: Rc<RefCell<State>>,
+}
+
+/// Wait Future
+///
+/// This future completes once all members of a [TaskGroup] are done.
+pub struct Wait {
+    state: Rc<RefCell<State>>,
+}
+
+//==============================================================================
+// Associate Functions
+//==============================================================================
+
+/// Associate Functions for Task Groups
+impl TaskGroup {
+    /// Creates an empty task group whose members run in `scheduler`.
+    pub fn new(scheduler: &Scheduler) -> Self {
+        Self {
+            scheduler: scheduler.clone(),
+            handles: Vec::new(),
+            state: Rc::new(RefCell::new(State::default())),
+        }
+    }
+
+    /// Inserts `future` as a new member of the target [TaskGroup], returning the key of the task. If the group was
+    /// cancelled, or the scheduler is full, `None` is returned.
+    #[track_caller]
+    pub fn insert<F: SchedulerFuture>(&mut self, future: F) -> Option<u64> {
+        if self.state.borrow().cancelled {
+            return None;
+        }
+        // Release the slots of members that completed.
+        self.handles.retain(|handle| !handle.has_completed());
+
+        self.state.borrow_mut().running += 1;
+        let member: GroupMember<F> = GroupMember {
+            future,
+            membership: Some(Membership {
+                state: self.state.clone(),
+            }),
+        };
+        let handle: SchedulerHandle = self.scheduler.insert(member)?;
+        let key: u64 = handle.key();
+        self.handles.push(handle);
+        Some(key)
+    }
+
+    /// Cancels all members of the target [TaskGroup] that are still running, and prevents new ones from being inserted.
+    /// Cancelled members are removed from the scheduler in its next poll. A member may cancel its own group, in which
+    /// case it is done once it returns from its poll.
+    pub fn cancel(&mut self) {
+        self.state.borrow_mut().cancelled = true;
+        for handle in mem::take(&mut self.handles) {
+            self.scheduler.cancel(&handle);
+        }
+    }
+
+    /// Queries whether or not the target [TaskGroup] was cancelled.
+    pub fn is_cancelled(&self) -> bool {
+        self.state.borrow().cancelled
+    }
+
+    /// Queries whether or not all members of the target [TaskGroup] are done, either because they completed or because
+    /// they were cancelled.
+    pub fn is_complete(&self) -> bool {
+        self.state.borrow().running == 0
+    }
+
+    /// Returns the number of members in the target [TaskGroup] that are still running.
+    pub fn running(&self) -> usize {
+        self.state.borrow().running
+    }
+
+    /// Returns a future that completes once all members of the target [TaskGroup] are done.
+    pub fn wait(&self) -> Wait {
+        Wait {
+            state: self.state.clone(),
+        }
+    }
+}
+
+//==============================================================================
+// Trait Implementations
+//==============================================================================
+
+/// Future Trait Implementation for Group Members
+impl<F: SchedulerFuture> Future for GroupMember<F> {
+    type Output = ();
+
+    /// Polls the target [GroupMember].
+    fn poll(self: Pin<&mut Self>, ctx: &mut Context) -> Poll<()> {
+        let self_: &mut GroupMember<F> = self.get_mut();
+        match Future::poll(Pin::new(&mut self_.future), ctx) {
+            Poll::Ready(()) => {
+                self_.membership.take();
+                Poll::Ready(())
+            },
+            Poll::Pending => Poll::Pending,
+        }
+    }
+}
+
+/// Scheduler Future Trait Implementation for Group Members
+impl<F: SchedulerFuture> SchedulerFuture for GroupMember<F> {
+    /// Casts the underlying future into [Any].
+    fn as_any(self: Box<Self>) -> Box<dyn Any> {
+        Box::new(self.future).as_any()
+    }
+
+    fn get_future(&self) -> &dyn Future<Output = ()> {
+        self.future.get_future()
+    }
+}
+
+/// Drop Trait Implementation for Memberships
+impl Drop for Membership {
+    fn drop(&mut self) {
+        let waiters: Vec<Waker> = {
+            let mut state: RefMut<State> = self.state.borrow_mut();
+            state.running -= 1;
+            match state.running {
+                0 => mem::take(&mut state.waiters),
+                _ => Vec::new(),
+            }
+        };
+        waiters.into_iter().for_each(Waker::wake);
+    }
+}
+
+/// Drop Trait Implementation for Task Groups
+impl Drop for TaskGroup {
+    /// Cancels members that are still running.
+    fn drop(&mut self) {
+        self.cancel();
+    }
+}
+
+/// Future Trait Implementation for Wait Futures
+impl Future for Wait {
+    type Output = ();
+
+    /// Polls the target [Wait].
+    fn poll(self: Pin<&mut Self>, ctx: &mut Context) -> Poll<()> {
+        let mut state: RefMut<State> = self.state.borrow_mut();
+        if state.running == 0 {
+            return Poll::Ready(());
+        }
+        if !state.waiters.iter().any(|waiter| waiter.will_wake(ctx.waker())) {
+            state.waiters.push(ctx.waker().clone());
+        }
+        Poll::Pending
+    }
+}
+
+//==============================================================================
+// Unit Tests
+//==============================================================================
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        test_utils::{
+            yield_now,
+            TestTask,
+        },
+        Scheduler,
+        SchedulerHandle,
+        TaskGroup,
+        Wait,
+    };
+    use ::std::{
+        cell::{
+            Cell,
+            RefCell,
+        },
+        future,
+        rc::Rc,
+    };
+
+    #[test]
+    fn group_completes() {
+        let scheduler: Scheduler = Scheduler::default();
+        let mut group: TaskGroup = TaskGroup::new(&scheduler);
+        for n in 0..3 {
+            group
+                .insert(TestTask::new(async move {
+                    for _ in 0..n {
+                        yield_now().await;
+                    }
+                }))
+                .expect("insert() failed");
+        }
+        assert_eq!(group.running(), 3);
+
+        scheduler.poll();
+        assert_eq!(group.running(), 2);
+        scheduler.poll();
+        scheduler.poll();
+        assert!(group.is_complete());
+        assert!(!group.is_cancelled());
+    }
+
+    #[test]
+    fn group_cancel() {
+        let scheduler: Scheduler = Scheduler::default();
+        let mut group: TaskGroup = TaskGroup::new(&scheduler);
+        let polls: Rc<Cell<usize>> = Rc::new(Cell::new(0));
+        let mut keys: Vec<u64> = Vec::new();
+        for _ in 0..3 {
+            let polls_: Rc<Cell<usize>> = polls.clone();
+            let key: u64 = group
+                .insert(TestTask::new(async move {
+                    polls_.set(polls_.get() + 1);
+                    future::pending::<()>().await;
+                }))
+                .expect("insert() failed");
+            keys.push(key);
+        }
+
+        scheduler.poll();
+        assert_eq!(polls.get(), 3);
+        group.cancel();
+        assert!(group.is_complete());
+        assert!(group.is_cancelled());
+        assert_eq!(group.insert(TestTask::new(async {})), None);
+
+        // Cancelled members are removed from the scheduler in its next poll.
+        scheduler.poll();
+        for key in keys {
+            assert!(scheduler.from_raw_handle(key).is_none());
+        }
+    }
+
+    #[test]
+    fn group_cancel_from_member() {
+        let scheduler: Scheduler = Scheduler::default();
+        let group: Rc<RefCell<TaskGroup>> = Rc::new(RefCell::new(TaskGroup::new(&scheduler)));
+        let after_cancel: Rc<Cell<bool>> = Rc::new(Cell::new(false));
+        group
+            .borrow_mut()
+            .insert(TestTask::new(future::pending::<()>()))
+            .expect("insert() failed");
+        let (group_, after_cancel_): (Rc<RefCell<TaskGroup>>, Rc<Cell<bool>>) = (group.clone(), after_cancel.clone());
+        let key: u64 = group
+            .borrow_mut()
+            .insert(TestTask::new(async move {
+                group_.borrow_mut().cancel();
+                // The member keeps on running until it returns from its poll.
+                after_cancel_.set(true);
+                future::pending::<()>().await;
+            }))
+            .expect("insert() failed");
+
+        scheduler.poll();
+        assert!(after_cancel.get());
+        assert!(group.borrow().is_cancelled());
+        assert!(group.borrow().is_complete());
+        scheduler.poll();
+        assert!(scheduler.from_raw_handle(key).is_none());
+    }
+
+    #[test]
+    fn group_wait() {
+        let scheduler: Scheduler = Scheduler::default();
+        let mut group: TaskGroup = TaskGroup::new(&scheduler);
+        for _ in 0..2 {
+            group
+                .insert(TestTask::new(async {
+                    yield_now().await;
+                }))
+                .expect("insert() failed");
+        }
+
+        let done: Rc<Cell<bool>> = Rc::new(Cell::new(false));
+        let done_: Rc<Cell<bool>> = done.clone();
+        let wait: Wait = group.wait();
+        let _waiter: SchedulerHandle = scheduler
+            .insert(TestTask::new(async move {
+                wait.await;
+                done_.set(true);
+            }))
+            .expect("insert() failed");
+
+        scheduler.poll();
+        assert!(!done.get());
+        scheduler.poll();
+        assert!(group.is_complete());
+        // The waiter was woken up when the last member completed.
+        scheduler.poll();
+        assert!(done.get());
+    }
+}
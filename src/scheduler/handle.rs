@@ -8,6 +8,11 @@
 use crate::{
     page::WakerPageRef,
     waker64::WAKER_BIT_LENGTH,
+    TaskStats,
+};
+use ::std::{
+    cell::Cell,
+    rc::Rc,
 };
 
 //==============================================================================
@@ -22,6 +27,8 @@ pub struct SchedulerHandle {
     key: Option<u64>,
     /// Memory chunk in which the corresponding handle lives.
     chunk: WakerPageRef,
+    /// Statistics of the corresponding future, shared with the scheduler, if accounted.
+    stats: Option<Rc<Cell<TaskStats>>>,
 }
 
 //==============================================================================
@@ -30,17 +37,33 @@ pub struct SchedulerHandle {
 
 /// Associate Functions for Scheduler Handlers
 impl SchedulerHandle {
-    /// Creates a new Scheduler Handle.
+    /// Creates a new Scheduler Handle. It is counted in the handles of `waker_page`.
     pub fn new(key: u64, waker_page: WakerPageRef) -> Self {
+        Self::with_stats(key, waker_page, None)
+    }
+
+    /// Creates a new Scheduler Handle that reads statistics from `stats`, if any.
+    pub(crate) fn with_stats(key: u64, waker_page: WakerPageRef, stats: Option<Rc<Cell<TaskStats>>>) -> Self {
+        waker_page.handles_inc();
         Self {
             key: Some(key),
             chunk: waker_page,
+            stats,
         }
     }
 
-    /// Takes out the key stored in the target [SchedulerHandle].
+    /// Takes out the key stored in the target [SchedulerHandle], which then no longer counts as a handle.
     pub fn take_key(&mut self) -> Option<u64> {
-        self.key.take()
+        let key: Option<u64> = self.key.take();
+        if key.is_some() {
+            self.chunk.handles_dec();
+        }
+        key
+    }
+
+    /// Returns the key of the future associated with the target [SchedulerHandle].
+    pub fn key(&self) -> u64 {
+        self.key.unwrap()
     }
 
     /// Queries whether or not the future associated with the target [SchedulerHandle] has complemented.
@@ -49,9 +72,15 @@ impl SchedulerHandle {
         self.chunk.has_completed(subpage_ix)
     }
 
+    /// Returns statistics of the future associated with the target [SchedulerHandle]. They are accumulated only if
+    /// accounting was enabled in the scheduler when the future was inserted.
+    pub fn stats(&self) -> TaskStats {
+        self.stats.as_ref().map_or_else(TaskStats::default, |stats| stats.get())
+    }
+
     /// Returns the raw key stored in the target [SchedulerHandle].
     pub fn into_raw(mut self) -> u64 {
-        self.key.take().unwrap()
+        self.take_key().unwrap()
     }
 }
 
@@ -63,7 +92,7 @@ impl SchedulerHandle {
 impl Drop for SchedulerHandle {
     /// Decreases the reference count of the target [SchedulerHandle].
     fn drop(&mut self) {
-        if let Some(key) = self.key.take() {
+        if let Some(key) = self.take_key() {
             let subpage_ix: usize = key as usize & (WAKER_BIT_LENGTH - 1);
             self.chunk.mark_dropped(subpage_ix);
         }
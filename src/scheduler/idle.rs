@@ -0,0 +1,237 @@
+// Copyright (c) Microsoft Corporation.
+// Licensed under the MIT license.
+
+//! Idle policies for the run loop of the [crate::Scheduler].
+//!
+//! An iteration of the run loop is idle if no task is notified once the poll
+//! is over. The policy decides what the loop does in idle iterations: keep
+//! spinning, give the core away, or sleep until something happens.
+
+//==============================================================================
+// Imports
+//==============================================================================
+
+use ::std::{
+    hint,
+    thread,
+    time::Duration,
+};
+
+//==============================================================================
+// Structures
+//==============================================================================
+
+/// Idle Policy
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum IdlePolicy {
+    /// Spins in every idle iteration.
+    Spin,
+    /// Spins for `spins` consecutive idle iterations, and then yields the thread in every following idle iteration.
+    SpinThenYield { spins: u32 },
+    /// Spins for `spins` consecutive idle iterations, and then parks the thread for at most `timeout` in every
+    /// following idle iteration. The thread is unparked as soon as some task is woken up.
+    #[cfg(target_os = "linux")]
+    SpinThenPark { spins: u32, timeout: Duration },
+}
+
+/// Action Taken in an Idle Iteration
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum IdleAction {
+    /// Spin.
+    Spin,
+    /// Yield the thread.
+    Yield,
+    /// Park the thread for at most some time.
+    Park(Duration),
+}
+
+/// Statistics of the Run Loop
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct IdleStats {
+    /// Number of iterations.
+    pub iterations: u64,
+    /// Number of idle iterations.
+    pub idle_iterations: u64,
+    /// Number of idle iterations in which the thread spun.
+    pub spins: u64,
+    /// Number of idle iterations in which the thread yielded.
+    pub yields: u64,
+    /// Number of idle iterations in which the thread parked.
+    pub parks: u64,
+}
+
+//==============================================================================
+// Associate Functions
+//==============================================================================
+
+/// Associate Functions for Idle Policies
+impl IdlePolicy {
+    /// Returns the action to take in the `idle`th consecutive idle iteration, counting from one.
+    pub fn action(&self, idle: u32) -> IdleAction {
+        match *self {
+            IdlePolicy::Spin => IdleAction::Spin,
+            IdlePolicy::SpinThenYield { spins } if idle <= spins => IdleAction::Spin,
+            IdlePolicy::SpinThenYield { .. } => IdleAction::Yield,
+            #[cfg(target_os = "linux")]
+            IdlePolicy::SpinThenPark { spins, .. } if idle <= spins => IdleAction::Spin,
+            #[cfg(target_os = "linux")]
+            IdlePolicy::SpinThenPark { timeout, .. } => IdleAction::Park(timeout),
+        }
+    }
+}
+
+/// Associate Functions for Idle Actions
+impl IdleAction {
+    /// Spins or yields, as dictated by the target [IdleAction]. Parking is up to the caller.
+    pub fn relax(&self) {
+        match self {
+            IdleAction::Spin => hint::spin_loop(),
+            IdleAction::Yield => thread::yield_now(),
+            IdleAction::Park(_) => (),
+        }
+    }
+}
+
+/// Associate Functions for Statistics of the Run Loop
+impl IdleStats {
+    /// Accounts for an iteration of the run loop, in which `action` was taken if it was idle.
+    pub fn record(&mut self, action: Option<IdleAction>) {
+        self.iterations += 1;
+        match action {
+            None => (),
+            Some(action) => {
+                self.idle_iterations += 1;
+                match action {
+                    IdleAction::Spin => self.spins += 1,
+                    IdleAction::Yield => self.yields += 1,
+                    IdleAction::Park(_) => self.parks += 1,
+                }
+            },
+        }
+    }
+}
+
+//==============================================================================
+// Trait Implementations
+//==============================================================================
+
+/// Default Trait Implementation for Idle Policies
+impl Default for IdlePolicy {
+    /// Creates a policy that always spins, as Demikernel deployments do.
+    fn default() -> Self {
+        IdlePolicy::Spin
+    }
+}
+
+//==============================================================================
+// Unit Tests
+//==============================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        IdleAction,
+        IdlePolicy,
+    };
+    use crate::{
+        test_utils::{
+            yield_now,
+            TestTask,
+        },
+        IdleStats,
+        ManualClock,
+        Scheduler,
+        SchedulerHandle,
+        Sleep,
+    };
+    use ::std::{
+        rc::Rc,
+        time::{
+            Duration,
+            Instant,
+        },
+    };
+
+    #[test]
+    fn idle_policy_actions() {
+        let policy: IdlePolicy = IdlePolicy::SpinThenYield { spins: 2 };
+        let actions: Vec<IdleAction> = (1..=3).map(|idle| policy.action(idle)).collect();
+        assert_eq!(actions, [IdleAction::Spin, IdleAction::Spin, IdleAction::Yield]);
+        assert_eq!(IdlePolicy::Spin.action(u32::MAX), IdleAction::Spin);
+    }
+
+    #[test]
+    fn run_spin() {
+        let scheduler: Scheduler = Scheduler::default();
+        let handle: SchedulerHandle = scheduler
+            .insert(TestTask::new(async {
+                for _ in 0..3 {
+                    yield_now().await;
+                }
+            }))
+            .expect("insert() failed");
+
+        scheduler
+            .run(IdlePolicy::Spin, || handle.has_completed())
+            .expect("run() failed");
+        // The loop does not idle once it is done.
+        let stats: IdleStats = scheduler.idle_stats();
+        assert_eq!(stats.iterations, 4);
+        assert_eq!(stats.idle_iterations, 0);
+        assert_eq!(stats.spins, 0);
+    }
+
+    #[test]
+    fn run_spin_then_yield() {
+        let scheduler: Scheduler = Scheduler::default();
+        let sleep: Sleep = scheduler.sleep(Duration::from_millis(5));
+        let handle: SchedulerHandle = scheduler.insert(TestTask::new(sleep)).expect("insert() failed");
+
+        scheduler
+            .run(IdlePolicy::SpinThenYield { spins: 10 }, || handle.has_completed())
+            .expect("run() failed");
+        let stats: IdleStats = scheduler.idle_stats();
+        assert_eq!(stats.spins, 10);
+        assert!(stats.yields > 0);
+        assert_eq!(stats.parks, 0);
+        assert_eq!(stats.idle_iterations, stats.spins + stats.yields);
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn run_spin_then_park() {
+        let scheduler: Scheduler = Scheduler::default();
+        let sleep: Sleep = scheduler.sleep(Duration::from_millis(20));
+        let handle: SchedulerHandle = scheduler.insert(TestTask::new(sleep)).expect("insert() failed");
+
+        let policy: IdlePolicy = IdlePolicy::SpinThenPark {
+            spins: 1,
+            timeout: Duration::from_secs(1),
+        };
+        scheduler.run(policy, || handle.has_completed()).expect("run() failed");
+        // Parking is bounded by the timer, so the loop does not sleep for the whole timeout.
+        let stats: IdleStats = scheduler.idle_stats();
+        assert_eq!(stats.spins, 1);
+        assert!(stats.parks >= 1);
+        assert!(stats.parks < 10);
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn run_spin_then_park_manual_clock() {
+        let clock: Rc<ManualClock> = Rc::new(ManualClock::new());
+        let scheduler: Scheduler = Scheduler::new(clock);
+        let sleep: Sleep = scheduler.sleep(Duration::from_secs(10));
+        let handle: SchedulerHandle = scheduler.insert(TestTask::new(sleep)).expect("insert() failed");
+
+        let policy: IdlePolicy = IdlePolicy::SpinThenPark {
+            spins: 1,
+            timeout: Duration::from_secs(1),
+        };
+        let start: Instant = Instant::now();
+        scheduler.run(policy, || handle.has_completed()).expect("run() failed");
+        // Parking moves the virtual clock ahead instead of blocking on wall time.
+        assert!(start.elapsed() < Duration::from_secs(1));
+        assert_eq!(scheduler.idle_stats().parks, 10);
+    }
+}
@@ -0,0 +1,355 @@
+// Copyright (c) Microsoft Corporation.
+// Licensed under the MIT license.
+
+//! Task-to-task joins for the [crate::Scheduler].
+//!
+//! A task that needs another task to complete awaits a [Join] on the handle of
+//! the latter. The awaiting task is woken up when the other task completes, or
+//! is cancelled, instead of spinning on [SchedulerHandle::has_completed].
+//!
+//! A [JoinSet] holds the handles of many tasks and gives them back in the
+//! order in which the tasks complete. Completed members are found through the
+//! completed flags of the pages they live in, rather than by scanning handles.
+
+//==============================================================================
+// Imports
+//==============================================================================
+
+use crate::{
+    consume_budget,
+    waker64::WAKER_BIT_LENGTH_SHIFT,
+    Scheduler,
+    SchedulerFuture,
+    SchedulerHandle,
+};
+use ::bit_iter::BitIter;
+use ::std::{
+    collections::{
+        HashMap,
+        VecDeque,
+    },
+    future::Future,
+    mem,
+    pin::Pin,
+    task::{
+        Context,
+        Poll,
+        Waker,
+    },
+};
+
+//==============================================================================
+// Structures
+//==============================================================================
+
+/// Join Future
+///
+/// This future completes once the task of a [SchedulerHandle] completes. The output of the task, if any, may then be
+/// retrieved through [Scheduler::take].
+pub struct Join<'a> {
+    /// Scheduler that runs the task.
+    scheduler: Scheduler,
+    /// Handle of the task.
+    handle: &'a SchedulerHandle,
+}
+
+/// Join Set
+///
+/// Dropping a join set drops the handles of its members, and thus removes them from the scheduler.
+pub struct JoinSet {
+    /// Scheduler that runs the members.
+    scheduler: Scheduler,
+    /// Handles of the members that are yet to be given back, by key.
+    members: HashMap<u64, SchedulerHandle>,
+    /// Flags of the members that have not been found completed yet, by page.
+    masks: HashMap<usize, u64>,
+    /// Handles of the members that were found completed, in completion order.
+    ready: VecDeque<SchedulerHandle>,
+    /// Waker of the task that awaits the next completed member, if any.
+    waker: Option<Waker>,
+    /// Keys of the members to which the waker is yet to be registered.
+    unregistered: Vec<u64>,
+}
+
+/// Join Next Future
+///
+/// This future completes with the handle of the next member of a [JoinSet] that completes, or with `None` if the set is
+/// empty.
+pub struct JoinNext<'a> {
+    set: &'a mut JoinSet,
+}
+
+//==============================================================================
+// Associate Functions
+//==============================================================================
+
+/// Associate Functions for Join Futures
+impl<'a> Join<'a> {
+    /// Creates a future that joins the task of `handle`, which runs in `scheduler`.
+    pub fn new(scheduler: Scheduler, handle: &'a SchedulerHandle) -> Self {
+        Self { scheduler, handle }
+    }
+}
+
+/// Associate Functions for Join Sets
+impl JoinSet {
+    /// Creates an empty join set whose members run in `scheduler`.
+    pub fn new(scheduler: &Scheduler) -> Self {
+        Self {
+            scheduler: scheduler.clone(),
+            members: HashMap::new(),
+            masks: HashMap::new(),
+            ready: VecDeque::new(),
+            waker: None,
+            unregistered: Vec::new(),
+        }
+    }
+
+    /// Inserts `future` in the scheduler, as a new member of the target [JoinSet]. The key of the task is returned, or
+    /// `None` if the scheduler is full.
+    #[track_caller]
+    pub fn insert<F: SchedulerFuture>(&mut self, future: F) -> Option<u64> {
+        let handle: SchedulerHandle = self.scheduler.insert(future)?;
+        let key: u64 = handle.key();
+        self.push(handle);
+        Some(key)
+    }
+
+    /// Adds the task of `handle` to the target [JoinSet].
+    pub fn push(&mut self, handle: SchedulerHandle) {
+        let key: u64 = handle.key();
+        let page_ix: usize = (key >> WAKER_BIT_LENGTH_SHIFT) as usize;
+        *self.masks.entry(page_ix).or_default() |= 1 << (key & ((1 << WAKER_BIT_LENGTH_SHIFT) - 1));
+        self.members.insert(key, handle);
+        self.unregistered.push(key);
+    }
+
+    /// Returns the number of members in the target [JoinSet] that are yet to be given back.
+    pub fn len(&self) -> usize {
+        self.members.len() + self.ready.len()
+    }
+
+    /// Queries whether or not the target [JoinSet] is empty.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Takes out the handle of the member of the target [JoinSet] that completed first, if any member has completed.
+    pub fn try_join_next(&mut self) -> Option<SchedulerHandle> {
+        if self.ready.is_empty() {
+            self.collect();
+        }
+        self.ready.pop_front()
+    }
+
+    /// Returns a future that completes with the handle of the next member of the target [JoinSet] that completes.
+    pub fn join_next(&mut self) -> JoinNext<'_> {
+        JoinNext { set: self }
+    }
+
+    /// Moves the members that completed since the last call to the ready queue, in completion order.
+    fn collect(&mut self) {
+        let mut completed: Vec<(u64, SchedulerHandle)> = Vec::new();
+        for (page_ix, mask) in self.masks.iter_mut() {
+            let done: u64 = self.scheduler.completed_in_page(*page_ix) & *mask;
+            *mask &= !done;
+            for subpage_ix in BitIter::from(done) {
+                let key: u64 = ((*page_ix << WAKER_BIT_LENGTH_SHIFT) + subpage_ix) as u64;
+                let handle: SchedulerHandle = self.members.remove(&key).unwrap();
+                completed.push((self.scheduler.completion_sequence(key), handle));
+            }
+        }
+        self.masks.retain(|_, mask| *mask != 0);
+        completed.sort_by_key(|(sequence, _)| *sequence);
+        self.ready.extend(completed.into_iter().map(|(_, handle)| handle));
+    }
+
+    /// Registers `waker` to be woken up once some member of the target [JoinSet] completes.
+    fn register(&mut self, waker: &Waker) {
+        if !matches!(&self.waker, Some(registered) if registered.will_wake(waker)) {
+            self.waker = Some(waker.clone());
+            self.unregistered = self.members.keys().copied().collect();
+        }
+        for key in mem::take(&mut self.unregistered) {
+            self.scheduler.add_joiner(key, waker);
+        }
+    }
+}
+
+//==============================================================================
+// Trait Implementations
+//==============================================================================
+
+/// Future Trait Implementation for Join Futures
+impl<'a> Future for Join<'a> {
+    type Output = ();
+
+    /// Polls the target [Join].
+    fn poll(self: Pin<&mut Self>, ctx: &mut Context) -> Poll<()> {
+        if consume_budget(ctx).is_pending() {
+            return Poll::Pending;
+        }
+        if self.handle.has_completed() {
+            return Poll::Ready(());
+        }
+        self.scheduler.add_joiner(self.handle.key(), ctx.waker());
+        Poll::Pending
+    }
+}
+
+/// Future Trait Implementation for Join Next Futures
+impl<'a> Future for JoinNext<'a> {
+    type Output = Option<SchedulerHandle>;
+
+    /// Polls the target [JoinNext].
+    fn poll(self: Pin<&mut Self>, ctx: &mut Context) -> Poll<Option<SchedulerHandle>> {
+        if consume_budget(ctx).is_pending() {
+            return Poll::Pending;
+        }
+        let set: &mut JoinSet = self.get_mut().set;
+        if let Some(handle) = set.try_join_next() {
+            return Poll::Ready(Some(handle));
+        }
+        if set.members.is_empty() {
+            return Poll::Ready(None);
+        }
+        set.register(ctx.waker());
+        Poll::Pending
+    }
+}
+
+//==============================================================================
+// Unit Tests
+//==============================================================================
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        test_utils::{
+            yield_now,
+            TestTask,
+        },
+        JoinSet,
+        Scheduler,
+        SchedulerHandle,
+    };
+    use ::std::{
+        cell::Cell,
+        future,
+        rc::Rc,
+    };
+
+    /// Inserts a task that joins `handle` and then sets `done`.
+    fn insert_joiner(scheduler: &Scheduler, handle: Rc<SchedulerHandle>, done: Rc<Cell<bool>>) -> SchedulerHandle {
+        let scheduler_: Scheduler = scheduler.clone();
+        scheduler
+            .insert(TestTask::new(async move {
+                scheduler_.join(&handle).await;
+                done.set(true);
+            }))
+            .expect("insert() failed")
+    }
+
+    #[test]
+    fn join_completed() {
+        let scheduler: Scheduler = Scheduler::default();
+        let handle: SchedulerHandle = scheduler
+            .insert(TestTask::new(async {
+                yield_now().await;
+                yield_now().await;
+            }))
+            .expect("insert() failed");
+        let done: Rc<Cell<bool>> = Rc::new(Cell::new(false));
+        let joiner: SchedulerHandle = insert_joiner(&scheduler, Rc::new(handle), done.clone());
+
+        scheduler.poll();
+        scheduler.poll();
+        scheduler.poll();
+        assert!(!done.get());
+        // The joiner was woken up when the other task completed.
+        scheduler.poll();
+        assert!(done.get());
+        assert!(joiner.has_completed());
+    }
+
+    #[test]
+    fn join_cancelled() {
+        let scheduler: Scheduler = Scheduler::default();
+        let handle: Rc<SchedulerHandle> = Rc::new(
+            scheduler
+                .insert(TestTask::new(future::pending::<()>()))
+                .expect("insert() failed"),
+        );
+        let done: Rc<Cell<bool>> = Rc::new(Cell::new(false));
+        let _joiner: SchedulerHandle = insert_joiner(&scheduler, handle.clone(), done.clone());
+
+        // The joiner is not polled again while the other task runs.
+        scheduler.poll();
+        assert!(!scheduler.has_notified());
+
+        // Cancelling the task wakes up the joiner.
+        scheduler.cancel(&handle);
+        assert!(scheduler.has_notified());
+        scheduler.poll();
+        assert!(done.get());
+    }
+
+    #[test]
+    fn join_set_completion_order() {
+        let scheduler: Scheduler = Scheduler::default();
+        let mut set: JoinSet = JoinSet::new(&scheduler);
+        let mut keys: Vec<u64> = Vec::new();
+        // Tasks are inserted in the reverse order of completion.
+        for n in (0..3).rev() {
+            let key: u64 = set
+                .insert(TestTask::new(async move {
+                    for _ in 0..n {
+                        yield_now().await;
+                    }
+                }))
+                .expect("insert() failed");
+            keys.push(key);
+        }
+        assert!(set.try_join_next().is_none());
+
+        for _ in 0..3 {
+            scheduler.poll();
+        }
+        let completed: Vec<u64> = (0..3).map(|_| set.try_join_next().unwrap().key()).collect();
+        keys.reverse();
+        assert_eq!(completed, keys);
+        assert!(set.is_empty());
+    }
+
+    #[test]
+    fn join_set_next() {
+        let scheduler: Scheduler = Scheduler::default();
+        let mut set: JoinSet = JoinSet::new(&scheduler);
+        for _ in 0..2 {
+            set.insert(TestTask::new(async {
+                yield_now().await;
+            }))
+            .expect("insert() failed");
+        }
+
+        // Accepts connections until the set is empty.
+        let accepted: Rc<Cell<usize>> = Rc::new(Cell::new(0));
+        let accepted_: Rc<Cell<usize>> = accepted.clone();
+        let acceptor: SchedulerHandle = scheduler
+            .insert(TestTask::new(async move {
+                while let Some(handle) = set.join_next().await {
+                    assert!(handle.has_completed());
+                    accepted_.set(accepted_.get() + 1);
+                }
+            }))
+            .expect("insert() failed");
+
+        scheduler.poll();
+        assert_eq!(accepted.get(), 0);
+        // Members complete in the second pass, which wakes up the acceptor.
+        scheduler.poll();
+        scheduler.poll();
+        assert_eq!(accepted.get(), 2);
+        assert!(acceptor.has_completed());
+    }
+}
@@ -0,0 +1,144 @@
+// Copyright (c) Microsoft Corporation.
+// Licensed under the MIT license.
+
+//! Leak reports for the [crate::Scheduler].
+//!
+//! Once enabled, a leak report is built when the last reference to a
+//! scheduler is dropped. It lists tasks that are still stored in the
+//! scheduler, and pages whose reference counts did not return to one after
+//! these tasks were dropped, which means that some handles or wakers of their
+//! tasks outlive the scheduler.
+
+//==============================================================================
+// Imports
+//==============================================================================
+
+use crate::TaskDescription;
+use ::std::fmt;
+
+//==============================================================================
+// Structures
+//==============================================================================
+
+/// Leaked Page
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PageLeak {
+    /// Index of the page.
+    pub page_ix: usize,
+    /// Reference count of the page, which is one when there is no leak.
+    pub refcount: u64,
+}
+
+/// Leak Report
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct LeakReport {
+    /// Tasks that were still stored in the scheduler.
+    pub tasks: Vec<TaskDescription>,
+    /// Pages that are still referenced from outside of the scheduler.
+    pub pages: Vec<PageLeak>,
+}
+
+/// Leak Report Callback
+pub type LeakReportFn = Box<dyn FnOnce(&LeakReport)>;
+
+//==============================================================================
+// Associate Functions
+//==============================================================================
+
+/// Associate Functions for Leak Reports
+impl LeakReport {
+    /// Queries whether or not the target [LeakReport] reports no leak.
+    pub fn is_empty(&self) -> bool {
+        self.tasks.is_empty() && self.pages.is_empty()
+    }
+}
+
+//==============================================================================
+// Trait Implementations
+//==============================================================================
+
+/// Display Trait Implementation for Leak Reports
+impl fmt::Display for LeakReport {
+    /// Writes one leak per line.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for task in &self.tasks {
+            writeln!(f, "leaked {}", task)?;
+        }
+        for page in &self.pages {
+            writeln!(f, "leaked {} references to page {}", page.refcount - 1, page.page_ix)?;
+        }
+        Ok(())
+    }
+}
+
+//==============================================================================
+// Unit Tests
+//==============================================================================
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        test_utils::TestTask,
+        LeakReport,
+        Scheduler,
+        SchedulerHandle,
+        TaskAttributes,
+    };
+    use ::std::{
+        cell::RefCell,
+        future::Future,
+        pin::Pin,
+        rc::Rc,
+        task::{
+            Context,
+            Poll,
+            Waker,
+        },
+    };
+
+    /// Future that hands its waker out, and never completes.
+    struct LeakWaker(Rc<RefCell<Option<Waker>>>);
+
+    impl Future for LeakWaker {
+        type Output = ();
+
+        fn poll(self: Pin<&mut Self>, ctx: &mut Context) -> Poll<()> {
+            *self.0.borrow_mut() = Some(ctx.waker().clone());
+            Poll::Pending
+        }
+    }
+
+    #[test]
+    fn leak_report() {
+        let report: Rc<RefCell<Option<LeakReport>>> = Rc::new(RefCell::new(None));
+        let waker: Rc<RefCell<Option<Waker>>> = Rc::new(RefCell::new(None));
+        {
+            let scheduler: Scheduler = Scheduler::default();
+            let report_: Rc<RefCell<Option<LeakReport>>> = report.clone();
+            scheduler.enable_leak_report(move |leaks: &LeakReport| *report_.borrow_mut() = Some(leaks.clone()));
+
+            let handle: SchedulerHandle = scheduler
+                .insert_with(TestTask::new(LeakWaker(waker.clone())), TaskAttributes::named("leaky"))
+                .expect("insert_with() failed");
+            scheduler.poll();
+            handle.into_raw();
+        }
+
+        let report: LeakReport = report.borrow_mut().take().expect("no leak was reported");
+        assert_eq!(report.tasks.len(), 1);
+        assert_eq!(report.tasks[0].name.as_deref(), Some("leaky"));
+        assert_eq!(report.pages.len(), 1);
+        assert_eq!(report.pages[0].refcount, 2);
+        assert!(report.to_string().contains("leaked 1 references to page 0"));
+        drop(waker);
+    }
+
+    #[test]
+    fn no_leak_report() {
+        let scheduler: Scheduler = Scheduler::default();
+        scheduler.enable_leak_report(|leaks: &LeakReport| panic!("unexpected leaks:\n{}", leaks));
+        let handle: SchedulerHandle = scheduler.insert(TestTask::new(async {})).expect("insert() failed");
+        scheduler.poll();
+        drop(scheduler.take(handle));
+    }
+}
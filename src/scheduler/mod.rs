@@ -1,18 +1,77 @@
 // Copyright (c) Microsoft Corporation.
 // Licensed under the MIT license.
 
+mod chaos;
+mod coop;
 mod future;
+mod group;
 mod handle;
+mod idle;
+mod join;
+mod leak;
+mod poller;
+mod record;
+#[cfg(target_os = "linux")]
+mod remote;
 mod result;
 mod scheduler;
+mod simulation;
+mod slow;
+mod stats;
+mod task;
+mod tree;
+mod validate;
 
 //==============================================================================
 // Exports
 //==============================================================================
 
 pub use self::{
+    chaos::ChaosConfig,
+    coop::{
+        consume_budget,
+        has_budget_remaining,
+    },
     future::SchedulerFuture,
+    group::{
+        TaskGroup,
+        Wait,
+    },
     handle::SchedulerHandle,
+    idle::{
+        IdleAction,
+        IdlePolicy,
+        IdleStats,
+    },
+    join::{
+        Join,
+        JoinNext,
+        JoinSet,
+    },
+    leak::{
+        LeakReport,
+        PageLeak,
+    },
+    poller::{
+        PollerContext,
+        PollerId,
+        PollerPhase,
+    },
+    record::{
+        Decision,
+        Recording,
+    },
     result::FutureResult,
     scheduler::Scheduler,
+    slow::SlowPoll,
+    stats::TaskStats,
+    task::{
+        TaskAttributes,
+        TaskDescription,
+    },
+    tree::TaskNode,
+    validate::{
+        TaskFlag,
+        Violation,
+    },
 };
@@ -0,0 +1,330 @@
+// Copyright (c) Microsoft Corporation.
+// Licensed under the MIT license.
+
+//! Pollers of the [crate::Scheduler].
+//!
+//! Pollers are closures that run in every call to [crate::Scheduler::poll],
+//! either before or after tasks are polled, regardless of whether any task is
+//! notified. They are meant for work that must happen once per iteration, such
+//! as draining a receive queue or flushing a batch of transmissions, and may
+//! wake up tasks that wait on such work.
+
+//==============================================================================
+// Imports
+//==============================================================================
+
+use crate::Scheduler;
+use ::std::mem;
+
+//==============================================================================
+// Structures
+//==============================================================================
+
+/// Phase in Which a Poller Runs
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PollerPhase {
+    /// Before tasks are polled. Tasks woken up by the poller are polled in the same pass.
+    BeforePoll,
+    /// After tasks are polled. Tasks woken up by the poller are polled in the next pass.
+    AfterPoll,
+}
+
+/// Identifier of a Registered Poller
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct PollerId(u64);
+
+/// Poller Context
+///
+/// This structure is handed over to pollers when they run.
+pub struct PollerContext<'a> {
+    scheduler: &'a Scheduler,
+}
+
+/// Poller Closure
+pub type PollerFn = Box<dyn FnMut(&PollerContext)>;
+
+/// Registered Pollers
+#[derive(Default)]
+pub struct Pollers {
+    /// Identifier of the next poller.
+    next_id: u64,
+    /// Pollers that run before tasks are polled.
+    before: Vec<(PollerId, PollerFn)>,
+    /// Pollers that run after tasks are polled.
+    after: Vec<(PollerId, PollerFn)>,
+    /// Identifiers of pollers that are running, and thus were taken out.
+    running: Vec<PollerId>,
+    /// Identifiers of running pollers that were unregistered.
+    unregistered: Vec<PollerId>,
+}
+
+//==============================================================================
+// Associate Functions
+//==============================================================================
+
+/// Associate Functions for Poller Contexts
+impl<'a> PollerContext<'a> {
+    /// Creates a poller context for `scheduler`.
+    pub fn new(scheduler: &'a Scheduler) -> Self {
+        Self { scheduler }
+    }
+
+    /// Returns the [Scheduler] that runs the poller.
+    pub fn scheduler(&self) -> &Scheduler {
+        self.scheduler
+    }
+
+    /// Wakes up the task identified by `key`. If there is no such task, `false` is returned.
+    pub fn wake(&self, key: u64) -> bool {
+        self.scheduler.wake_raw(key)
+    }
+}
+
+/// Associate Functions for Registered Pollers
+impl Pollers {
+    /// Registers `poller` to run in `phase`.
+    pub fn register(&mut self, phase: PollerPhase, poller: PollerFn) -> PollerId {
+        let id: PollerId = PollerId(self.next_id);
+        self.next_id += 1;
+        self.phase_mut(phase).push((id, poller));
+        id
+    }
+
+    /// Unregisters the poller identified by `id`. If there is no such poller, `false` is returned.
+    pub fn unregister(&mut self, id: PollerId) -> bool {
+        for pollers in [&mut self.before, &mut self.after] {
+            if let Some(ix) = pollers.iter().position(|(poller_id, _)| *poller_id == id) {
+                drop(pollers.remove(ix));
+                return true;
+            }
+        }
+        // The poller may be running, in which case it is removed when it is restored.
+        if self.running.contains(&id) && !self.unregistered.contains(&id) {
+            self.unregistered.push(id);
+            return true;
+        }
+        false
+    }
+
+    /// Takes out the pollers that run in `phase`, so that they may run without borrowing the scheduler.
+    pub fn take(&mut self, phase: PollerPhase) -> Vec<(PollerId, PollerFn)> {
+        let pollers: Vec<(PollerId, PollerFn)> = mem::take(self.phase_mut(phase));
+        self.running.extend(pollers.iter().map(|(id, _)| *id));
+        pollers
+    }
+
+    /// Puts back `pollers` that were taken out for `phase`. Pollers that were registered in the meantime run after
+    /// them, and pollers that were unregistered in the meantime are dropped.
+    pub fn restore(&mut self, phase: PollerPhase, mut pollers: Vec<(PollerId, PollerFn)>) {
+        let unregistered: Vec<PollerId> = mem::take(&mut self.unregistered);
+        self.running.clear();
+        pollers.retain(|(id, _)| !unregistered.contains(id));
+        let registered: &mut Vec<(PollerId, PollerFn)> = self.phase_mut(phase);
+        pollers.append(registered);
+        *registered = pollers;
+    }
+
+    /// Queries whether or not some pollers are running, and thus were taken out.
+    pub fn is_running(&self) -> bool {
+        !self.running.is_empty()
+    }
+
+    /// Queries whether or not no poller is registered.
+    pub fn is_empty(&self) -> bool {
+        self.before.is_empty() && self.after.is_empty() && self.running.is_empty()
+    }
+
+    /// Returns the pollers that run in `phase`.
+    fn phase_mut(&mut self, phase: PollerPhase) -> &mut Vec<(PollerId, PollerFn)> {
+        match phase {
+            PollerPhase::BeforePoll => &mut self.before,
+            PollerPhase::AfterPoll => &mut self.after,
+        }
+    }
+}
+
+//==============================================================================
+// Unit Tests
+//==============================================================================
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        test_utils::TestTask,
+        PollerContext,
+        PollerId,
+        PollerPhase,
+        Scheduler,
+        SchedulerHandle,
+    };
+    use ::std::{
+        cell::{
+            Cell,
+            RefCell,
+            RefMut,
+        },
+        future::Future,
+        panic::{
+            self,
+            AssertUnwindSafe,
+        },
+        pin::Pin,
+        rc::Rc,
+        task::{
+            Context,
+            Poll,
+        },
+    };
+
+    /// Future that completes once a queue holds some item.
+    struct Recv {
+        queue: Rc<RefCell<Vec<u32>>>,
+    }
+
+    impl Future for Recv {
+        type Output = u32;
+
+        fn poll(self: Pin<&mut Self>, _ctx: &mut Context) -> Poll<u32> {
+            // Wakeups come from the poller, not from the queue.
+            match self.queue.borrow_mut().pop() {
+                Some(item) => Poll::Ready(item),
+                None => Poll::Pending,
+            }
+        }
+    }
+
+    #[test]
+    fn poller_wakes_task() {
+        let scheduler: Scheduler = Scheduler::default();
+        let queue: Rc<RefCell<Vec<u32>>> = Rc::new(RefCell::new(Vec::new()));
+        let received: Rc<Cell<Option<u32>>> = Rc::new(Cell::new(None));
+
+        let recv: Recv = Recv { queue: queue.clone() };
+        let received_: Rc<Cell<Option<u32>>> = received.clone();
+        let handle: SchedulerHandle = scheduler
+            .insert(TestTask::new(async move {
+                received_.set(Some(recv.await));
+            }))
+            .expect("insert() failed");
+
+        // Emulates the receive queue of a NIC.
+        let key: u64 = handle.key();
+        let queue_: Rc<RefCell<Vec<u32>>> = queue;
+        let nic: Rc<RefCell<Vec<u32>>> = Rc::new(RefCell::new(Vec::new()));
+        let nic_: Rc<RefCell<Vec<u32>>> = nic.clone();
+        scheduler.register_poller(PollerPhase::BeforePoll, move |ctx: &PollerContext| {
+            let mut nic: RefMut<Vec<u32>> = nic_.borrow_mut();
+            if !nic.is_empty() {
+                queue_.borrow_mut().append(&mut nic);
+                assert!(ctx.wake(key));
+            }
+        });
+
+        scheduler.poll();
+        scheduler.poll();
+        assert_eq!(received.get(), None);
+
+        // The task is polled in the same pass in which the poller wakes it up.
+        nic.borrow_mut().push(42);
+        scheduler.poll();
+        assert_eq!(received.get(), Some(42));
+        assert!(handle.has_completed());
+    }
+
+    #[test]
+    fn poller_phases() {
+        let scheduler: Scheduler = Scheduler::default();
+        let log: Rc<RefCell<Vec<&'static str>>> = Rc::new(RefCell::new(Vec::new()));
+
+        let log_: Rc<RefCell<Vec<&'static str>>> = log.clone();
+        scheduler.register_poller(PollerPhase::AfterPoll, move |_: &PollerContext| {
+            log_.borrow_mut().push("after")
+        });
+        let log_: Rc<RefCell<Vec<&'static str>>> = log.clone();
+        let _handle: SchedulerHandle = scheduler
+            .insert(TestTask::new(async move { log_.borrow_mut().push("task") }))
+            .expect("insert() failed");
+        let log_: Rc<RefCell<Vec<&'static str>>> = log.clone();
+        scheduler.register_poller(PollerPhase::BeforePoll, move |_: &PollerContext| {
+            log_.borrow_mut().push("before")
+        });
+
+        scheduler.poll();
+        scheduler.poll();
+        assert_eq!(*log.borrow(), ["before", "task", "after", "before", "after"]);
+    }
+
+    #[test]
+    fn poller_unregister() {
+        let scheduler: Scheduler = Scheduler::default();
+        let runs: Rc<Cell<usize>> = Rc::new(Cell::new(0));
+
+        // A poller that unregisters itself after running twice.
+        let id: Rc<Cell<Option<PollerId>>> = Rc::new(Cell::new(None));
+        let (runs_, id_): (Rc<Cell<usize>>, Rc<Cell<Option<PollerId>>>) = (runs.clone(), id.clone());
+        id.set(Some(scheduler.register_poller(
+            PollerPhase::BeforePoll,
+            move |ctx: &PollerContext| {
+                runs_.set(runs_.get() + 1);
+                if runs_.get() == 2 {
+                    assert!(ctx.scheduler().unregister_poller(id_.get().unwrap()));
+                }
+            },
+        )));
+
+        for _ in 0..4 {
+            scheduler.poll();
+        }
+        assert_eq!(runs.get(), 2);
+        assert!(!scheduler.unregister_poller(id.get().unwrap()));
+    }
+
+    #[test]
+    fn poller_panics() {
+        let scheduler: Scheduler = Scheduler::default();
+        let (first, second): (Rc<Cell<usize>>, Rc<Cell<usize>>) = (Rc::new(Cell::new(0)), Rc::new(Cell::new(0)));
+
+        // The first poller panics in its second run.
+        let first_: Rc<Cell<usize>> = first.clone();
+        scheduler.register_poller(PollerPhase::BeforePoll, move |_: &PollerContext| {
+            first_.set(first_.get() + 1);
+            assert!(first_.get() != 2, "poller failed");
+        });
+        let second_: Rc<Cell<usize>> = second.clone();
+        scheduler.register_poller(PollerPhase::BeforePoll, move |_: &PollerContext| {
+            second_.set(second_.get() + 1);
+        });
+
+        scheduler.poll();
+        let scheduler_: Scheduler = scheduler.clone();
+        assert!(panic::catch_unwind(AssertUnwindSafe(|| scheduler_.poll())).is_err());
+        assert_eq!((first.get(), second.get()), (2, 1));
+
+        // Both pollers are still registered.
+        scheduler.poll();
+        assert_eq!((first.get(), second.get()), (3, 2));
+    }
+
+    #[test]
+    fn poller_nested_poll() {
+        let scheduler: Scheduler = Scheduler::default();
+        let runs: Rc<Cell<usize>> = Rc::new(Cell::new(0));
+        let polls: Rc<Cell<usize>> = Rc::new(Cell::new(0));
+
+        let polls_: Rc<Cell<usize>> = polls.clone();
+        let _handle: SchedulerHandle = scheduler
+            .insert(TestTask::new(async move { polls_.set(polls_.get() + 1) }))
+            .expect("insert() failed");
+        let runs_: Rc<Cell<usize>> = runs.clone();
+        scheduler.register_poller(PollerPhase::BeforePoll, move |ctx: &PollerContext| {
+            runs_.set(runs_.get() + 1);
+            // The nested poll runs tasks, but not pollers.
+            ctx.scheduler().poll();
+        });
+
+        scheduler.poll();
+        scheduler.poll();
+        assert_eq!(runs.get(), 2);
+        assert_eq!(polls.get(), 1);
+    }
+}
@@ -0,0 +1,229 @@
+// Copyright (c) Microsoft Corporation.
+// Licensed under the MIT license.
+
+//! Record and replay of scheduling decisions.
+//!
+//! When recording, the [crate::Scheduler] logs every task that it polls, along
+//! with the poll round in which it did so and the result of the poll. When
+//! replaying, the scheduler polls exactly the tasks in the log, in the same
+//! order, regardless of which tasks were notified.
+//!
+//! Recordings are serialized into a compact binary format: a header, followed
+//! by one entry per decision, where each entry holds the round (as a delta to
+//! the previous decision) and the key of the task (both LEB128-encoded), and a
+//! byte with the result of the poll.
+
+//==============================================================================
+// Imports
+//==============================================================================
+
+use ::std::collections::VecDeque;
+
+//==============================================================================
+// Constants
+//==============================================================================
+
+/// Header of serialized recordings.
+const MAGIC: &[u8; 4] = b"SCHR";
+
+/// Version of the serialization format.
+const VERSION: u8 = 1;
+
+//==============================================================================
+// Structures
+//==============================================================================
+
+/// Scheduling Decision
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Decision {
+    /// Poll round in which the decision was made, counting from the start of the recording.
+    pub round: u64,
+    /// Key of the task that was polled.
+    pub key: u64,
+    /// Whether or not the task completed when polled.
+    pub ready: bool,
+}
+
+/// Recording
+///
+/// A sequence of scheduling decisions.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Recording {
+    decisions: Vec<Decision>,
+}
+
+/// Replay State
+pub struct Replay {
+    /// Decisions that are yet to be replayed.
+    decisions: VecDeque<Decision>,
+    /// Poll round in which the replay started.
+    base_round: u64,
+}
+
+//==============================================================================
+// Associate Functions
+//==============================================================================
+
+/// Associate Functions for Recordings
+impl Recording {
+    /// Returns the decisions in the target [Recording].
+    pub fn decisions(&self) -> &[Decision] {
+        &self.decisions
+    }
+
+    /// Appends `decision` to the target [Recording]. Decisions must be appended in the order of their poll rounds,
+    /// otherwise this panics.
+    pub fn push(&mut self, decision: Decision) {
+        if let Some(last) = self.decisions.last() {
+            assert!(
+                decision.round >= last.round,
+                "decision of round {} follows round {}",
+                decision.round,
+                last.round
+            );
+        }
+        self.decisions.push(decision);
+    }
+
+    /// Serializes the target [Recording].
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes: Vec<u8> = Vec::with_capacity(MAGIC.len() + 1 + 3 * self.decisions.len());
+        bytes.extend_from_slice(MAGIC);
+        bytes.push(VERSION);
+        let mut last_round: u64 = 0;
+        for decision in &self.decisions {
+            write_varint(&mut bytes, decision.round - last_round);
+            write_varint(&mut bytes, decision.key);
+            bytes.push(decision.ready as u8);
+            last_round = decision.round;
+        }
+        bytes
+    }
+
+    /// Deserializes a recording from `bytes`. If `bytes` is malformed, `None` is returned.
+    pub fn from_bytes(bytes: &[u8]) -> Option<Self> {
+        let mut bytes: &[u8] = bytes.strip_prefix(MAGIC)?.strip_prefix(&[VERSION])?;
+        let mut decisions: Vec<Decision> = Vec::new();
+        let mut round: u64 = 0;
+        while !bytes.is_empty() {
+            round = round.checked_add(read_varint(&mut bytes)?)?;
+            let key: u64 = read_varint(&mut bytes)?;
+            let (ready, rest): (&u8, &[u8]) = bytes.split_first()?;
+            let ready: bool = match ready {
+                0 => false,
+                1 => true,
+                _ => return None,
+            };
+            bytes = rest;
+            decisions.push(Decision { round, key, ready });
+        }
+        Some(Self { decisions })
+    }
+}
+
+/// Associate Functions for Replays
+impl Replay {
+    /// Creates a replay of `recording` that starts after the `base_round`th poll round.
+    pub fn new(recording: Recording, base_round: u64) -> Self {
+        Self {
+            decisions: recording.decisions.into(),
+            base_round,
+        }
+    }
+
+    /// Takes out the decisions for the `round`th poll round.
+    pub fn take_round(&mut self, round: u64) -> Vec<Decision> {
+        let round: u64 = round - self.base_round;
+        let mut decisions: Vec<Decision> = Vec::new();
+        while let Some(decision) = self.decisions.front() {
+            if decision.round > round {
+                break;
+            }
+            decisions.extend(self.decisions.pop_front());
+        }
+        decisions
+    }
+
+    /// Queries whether or not all decisions were replayed.
+    pub fn is_done(&self) -> bool {
+        self.decisions.is_empty()
+    }
+}
+
+//==============================================================================
+// Standalone Functions
+//==============================================================================
+
+/// Appends `value` to `bytes`, using LEB128 encoding.
+fn write_varint(bytes: &mut Vec<u8>, mut value: u64) {
+    while value >= 0x80 {
+        bytes.push((value as u8) | 0x80);
+        value >>= 7;
+    }
+    bytes.push(value as u8);
+}
+
+/// Reads a LEB128-encoded value from the front of `bytes`.
+fn read_varint(bytes: &mut &[u8]) -> Option<u64> {
+    let mut value: u64 = 0;
+    for shift in (0..64).step_by(7) {
+        let (byte, rest): (&u8, &[u8]) = bytes.split_first()?;
+        *bytes = rest;
+        value |= ((byte & 0x7f) as u64) << shift;
+        if byte & 0x80 == 0 {
+            return Some(value);
+        }
+    }
+    None
+}
+
+//==============================================================================
+// Unit Tests
+//==============================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        Decision,
+        Recording,
+    };
+
+    #[test]
+    fn recording_round_trip() {
+        let mut recording: Recording = Recording::default();
+        for (round, key, ready) in [(1, 0, false), (1, 300, true), (2, 0, false), (70_000, u64::MAX, true)] {
+            recording.push(Decision { round, key, ready });
+        }
+
+        let bytes: Vec<u8> = recording.to_bytes();
+        assert_eq!(Recording::from_bytes(&bytes), Some(recording));
+    }
+
+    #[test]
+    fn recording_malformed() {
+        let mut recording: Recording = Recording::default();
+        recording.push(Decision {
+            round: 1,
+            key: 1_000,
+            ready: false,
+        });
+        let bytes: Vec<u8> = recording.to_bytes();
+
+        assert_eq!(Recording::from_bytes(&bytes[1..]), None);
+        assert_eq!(Recording::from_bytes(&bytes[..bytes.len() - 1]), None);
+        assert_eq!(Recording::from_bytes(b"SCHR\x02"), None);
+    }
+
+    #[test]
+    #[should_panic(expected = "decision of round 1 follows round 2")]
+    fn recording_rounds_out_of_order() {
+        let mut recording: Recording = Recording::default();
+        for round in [2, 1] {
+            recording.push(Decision {
+                round,
+                key: 0,
+                ready: false,
+            });
+        }
+    }
+}
@@ -0,0 +1,130 @@
+// Copyright (c) Microsoft Corporation.
+// Licensed under the MIT license.
+
+//! Cross-thread wakeups for the [crate::Scheduler].
+//!
+//! The status of tasks lives in [crate::page::WakerPage]s, which may only be
+//! touched by the thread that runs the scheduler. Remote wakers instead push
+//! the key of their task into a shared queue and unpark the scheduler. The
+//! queue is drained at the beginning of every poll.
+
+//==============================================================================
+// Imports
+//==============================================================================
+
+use crate::reactor::{
+    Parker,
+    Unparker,
+};
+use ::std::{
+    io,
+    mem,
+    sync::{
+        Arc,
+        Mutex,
+    },
+    task::{
+        Wake,
+        Waker,
+    },
+    time::Duration,
+};
+
+//==============================================================================
+// Structures
+//==============================================================================
+
+/// Queue of Remote Wakeups
+struct Queue {
+    /// Keys of tasks that were woken up.
+    keys: Mutex<Vec<u64>>,
+    /// Wakes up the scheduler.
+    unparker: Unparker,
+}
+
+/// Remote Waker
+struct RemoteWaker {
+    /// Key of the target task.
+    key: u64,
+    /// Queue that wakeups are pushed into.
+    queue: Arc<Queue>,
+}
+
+/// Remote Wakeup State
+pub struct Remote {
+    /// Parks the scheduler.
+    parker: Parker,
+    /// Queue of wakeups.
+    queue: Arc<Queue>,
+    /// Remote wakers of tasks, by key.
+    wakers: Vec<Option<Waker>>,
+}
+
+//==============================================================================
+// Associate Functions
+//==============================================================================
+
+/// Associate Functions for Remote Wakeup State
+impl Remote {
+    /// Creates a remote wakeup state.
+    pub fn new() -> io::Result<Self> {
+        let parker: Parker = Parker::new()?;
+        let queue: Queue = Queue {
+            keys: Mutex::new(Vec::new()),
+            unparker: parker.unparker(),
+        };
+        Ok(Self {
+            parker,
+            queue: Arc::new(queue),
+            wakers: Vec::new(),
+        })
+    }
+
+    /// Returns a thread-safe waker for the task identified by `key`.
+    pub fn waker(&mut self, key: u64) -> Waker {
+        let ix: usize = key as usize;
+        if self.wakers.len() <= ix {
+            self.wakers.resize(ix + 1, None);
+        }
+        let queue: &Arc<Queue> = &self.queue;
+        self.wakers[ix]
+            .get_or_insert_with(|| {
+                Waker::from(Arc::new(RemoteWaker {
+                    key,
+                    queue: queue.clone(),
+                }))
+            })
+            .clone()
+    }
+
+    /// Takes out the keys of tasks that were woken up.
+    pub fn drain(&self) -> Vec<u64> {
+        mem::take(&mut *self.queue.keys.lock().expect("poisoned lock"))
+    }
+
+    /// Queries whether or not some wakeup is waiting to be drained.
+    pub fn has_pending(&self) -> bool {
+        !self.queue.keys.lock().expect("poisoned lock").is_empty()
+    }
+
+    /// Parks the current thread until some task is woken up or `timeout` elapses.
+    pub fn park(&self, timeout: Option<Duration>) -> io::Result<()> {
+        self.parker.park(timeout)
+    }
+}
+
+//==============================================================================
+// Trait Implementations
+//==============================================================================
+
+/// Wake Trait Implementation for Remote Wakers
+impl Wake for RemoteWaker {
+    fn wake(self: Arc<Self>) {
+        self.wake_by_ref();
+    }
+
+    fn wake_by_ref(self: &Arc<Self>) {
+        self.queue.keys.lock().expect("poisoned lock").push(self.key);
+        self.queue.unparker.unpark();
+    }
+}
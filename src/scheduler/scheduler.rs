@@ -5,18 +5,82 @@
 //!
 //! Our scheduler uses a pinned memory slab to store tasks ([SchedulerFuture]s).
 //! As background tasks are polled, they notify task in our scheduler via the
-//! [crate::page::WakerPage]s.
+//! [crate::page::WakerPage]s. Timers are fired by the scheduler, through its
+//! [Timer], at the beginning of every poll.
 
 //==============================================================================
 // Imports
 //==============================================================================
 
+#[cfg(target_os = "linux")]
+use crate::scheduler::remote::Remote;
 use crate::{
     page::{
         WakerPageRef,
         WakerRef,
     },
     pin_slab::PinSlab,
+    scheduler::{
+        chaos::{
+            Chaos,
+            ChaosConfig,
+        },
+        coop,
+        idle::{
+            IdleAction,
+            IdlePolicy,
+            IdleStats,
+        },
+        join::Join,
+        leak::{
+            LeakReport,
+            LeakReportFn,
+            PageLeak,
+        },
+        poller::{
+            PollerContext,
+            PollerFn,
+            PollerId,
+            PollerPhase,
+            Pollers,
+        },
+        record::{
+            Decision,
+            Recording,
+            Replay,
+        },
+        simulation::{
+            SeedReporter,
+            Simulation,
+        },
+        slow::{
+            SlowPoll,
+            SlowPollFn,
+            SlowPolls,
+        },
+        stats::TaskStats,
+        task::{
+            TaskAttributes,
+            TaskDescription,
+        },
+        tree::{
+            Cancelled,
+            TaskNode,
+            TaskTree,
+        },
+        validate::{
+            TaskFlag,
+            Violation,
+        },
+    },
+    timer::{
+        Clock,
+        Interval,
+        Sleep,
+        SystemClock,
+        Timeout,
+        Timer,
+    },
     waker64::{
         WAKER_BIT_LENGTH,
         WAKER_BIT_LENGTH_SHIFT,
@@ -26,12 +90,19 @@ use crate::{
 };
 use ::bit_iter::BitIter;
 use ::std::{
+    any,
     cell::{
+        Cell,
         Ref,
         RefCell,
         RefMut,
     },
+    collections::HashMap,
+    fmt,
     future::Future,
+    io,
+    mem,
+    panic::Location,
     pin::Pin,
     ptr::NonNull,
     rc::Rc,
@@ -40,24 +111,98 @@ use ::std::{
         Poll,
         Waker,
     },
+    time::{
+        Duration,
+        Instant,
+    },
 };
 
 //==============================================================================
 // Structures
 //==============================================================================
 
+/// Metadata of a Task
+#[derive(Default)]
+struct TaskInfo {
+    /// Name of the task, if any.
+    name: Option<Rc<str>>,
+    /// Type name of the future.
+    type_name: &'static str,
+    /// Location from which the task was inserted.
+    location: Option<&'static Location<'static>>,
+    /// Tag of the task, if any.
+    tag: Option<Rc<str>>,
+    /// Statistics of the task, shared with its handles, if accounting was enabled when it was inserted.
+    stats: Option<Rc<Cell<TaskStats>>>,
+}
+
 /// Actual data used by [Scheduler].
 struct Inner<F: Future<Output = ()> + Unpin> {
     /// Stores all the tasks that are held by the scheduler.
     slab: PinSlab<F>,
     /// Holds the status tasks.
     pages: Vec<WakerPageRef>,
+    /// Metadata of tasks, by key.
+    tasks: Vec<TaskInfo>,
+    /// Number of poll rounds run so far.
+    round: u64,
+    /// Deterministic simulation, if enabled.
+    simulation: Option<Simulation>,
+    /// Chaos, if enabled.
+    chaos: Option<Chaos>,
+    /// Recording of scheduling decisions, along with the poll round in which it started, if enabled.
+    recording: Option<(Recording, u64)>,
+    /// Replay of scheduling decisions, if any.
+    replay: Option<Replay>,
+    /// Statistics of the run loop.
+    idle_stats: IdleStats,
+    /// Registered pollers.
+    pollers: Pollers,
+    /// Parent/child links of tasks.
+    tree: TaskTree,
+    /// Key of the task that is being polled, if any.
+    current: Option<u64>,
+    /// Whether or not the task that is being polled was cancelled. Its future is replaced once the poll returns.
+    current_cancelled: bool,
+    /// Futures of cancelled tasks that are yet to be dropped, outside of the borrow of the scheduler.
+    cancelled: Vec<F>,
+    /// Wakers of tasks that join other tasks, by key of the joined task.
+    joiners: HashMap<u64, Vec<Waker>>,
+    /// Wakers of tasks whose joined task completed, that are yet to be woken up outside of the borrow of the scheduler.
+    woken: Vec<Waker>,
+    /// Number of tasks that completed so far.
+    completions: u64,
+    /// Sequence number in which tasks completed, by key.
+    sequence: Vec<u64>,
+    /// Keys of tasks that completed since the queue was last drained, if enabled.
+    completion_queue: Option<Vec<u64>>,
+    /// Cooperative budget of tasks in each poll, if constrained.
+    coop_budget: Option<u32>,
+    /// Slow-poll detection, if enabled.
+    slow_polls: Option<SlowPolls>,
+    /// Statistics of tasks by tag, if accounting is enabled.
+    tag_stats: Option<HashMap<Rc<str>, TaskStats>>,
+    /// Callback to which leaks are reported when dropped, if enabled.
+    leak_report: Option<LeakReportFn>,
+    /// Cross-thread wakeups, if enabled.
+    #[cfg(target_os = "linux")]
+    remote: Option<Remote>,
 }
 
 /// Future Scheduler
 #[derive(Clone)]
 pub struct Scheduler {
     inner: Rc<RefCell<Inner<Box<dyn SchedulerFuture>>>>,
+    /// Drives timers of tasks.
+    timer: Timer,
+}
+
+/// Puts pollers that were taken out of a [Scheduler] back when dropped, so that they are restored even if one of them
+/// panics.
+struct RestorePollers<'a> {
+    scheduler: &'a Scheduler,
+    phase: PollerPhase,
+    pollers: Vec<(PollerId, PollerFn)>,
 }
 
 //==============================================================================
@@ -73,237 +218,1996 @@ impl<F: Future<Output = ()> + Unpin> Inner<F> {
         (&self.pages[page_ix], subpage_ix)
     }
 
-    /// Insert a task into our scheduler returning a key that may be used to drive its status.
-    fn insert(&mut self, future: F) -> Option<u64> {
+    /// Describes the task identified by `key`.
+    fn describe(&self, key: u64) -> TaskDescription {
+        let info: &TaskInfo = &self.tasks[key as usize];
+        let (page, subpage_ix): (&WakerPageRef, usize) = self.get_page(key);
+        TaskDescription {
+            key,
+            name: info.name.clone(),
+            tag: info.tag.clone(),
+            type_name: info.type_name,
+            location: info.location,
+            completed: page.has_completed(subpage_ix),
+        }
+    }
+
+    /// Cross-checks the slab, the pages and the tree of tasks, returning violations of invariants.
+    fn validate(&self) -> Vec<Violation> {
+        let mut violations: Vec<Violation> = Vec::new();
+        // Keys of dropped tasks and number of tasks that were not dropped, by page.
+        let mut dropped: Vec<Vec<u64>> = vec![Vec::new(); self.pages.len()];
+        let mut undropped: Vec<u64> = vec![0; self.pages.len()];
+        let len: usize = self.tasks.len().max(self.pages.len() << WAKER_BIT_LENGTH_SHIFT);
+        for key in 0..len as u64 {
+            let (page_ix, subpage_ix): (usize, usize) = (
+                key as usize >> WAKER_BIT_LENGTH_SHIFT,
+                key as usize & (WAKER_BIT_LENGTH - 1),
+            );
+            let occupied: bool = self.slab.get(key as usize).is_some();
+            if occupied && page_ix >= self.pages.len() {
+                violations.push(Violation::MissingPage { key });
+                continue;
+            }
+
+            if !occupied {
+                if page_ix < self.pages.len() {
+                    let page: &WakerPageRef = &self.pages[page_ix];
+                    let flags: [(bool, TaskFlag); 3] = [
+                        (page.has_completed(subpage_ix), TaskFlag::Completed),
+                        (page.was_dropped(subpage_ix), TaskFlag::Dropped),
+                        (page.is_suspended(subpage_ix), TaskFlag::Suspended),
+                    ];
+                    for (_, flag) in flags.into_iter().filter(|(set, _)| *set) {
+                        violations.push(Violation::VacantFlag { key, flag });
+                    }
+                }
+                continue;
+            }
+
+            // Stale notifications of completed tasks are legal, since they are masked when notifications are taken.
+            let page: &WakerPageRef = &self.pages[page_ix];
+            if page.was_dropped(subpage_ix) {
+                dropped[page_ix].push(key);
+            } else {
+                undropped[page_ix] += 1;
+            }
+            if let Some(parent) = self.tree.parent(key) {
+                let linked: bool =
+                    self.slab.get(parent as usize).is_some() && self.tree.children(parent).contains(&key);
+                if !linked {
+                    violations.push(Violation::BrokenLink { key, parent });
+                }
+            }
+        }
+
+        for (page_ix, page) in self.pages.iter().enumerate() {
+            // A task has at most one handle, which sets the dropped flag once dropped.
+            let handles: u64 = page.handles_get();
+            let excess: u64 = handles.saturating_sub(undropped[page_ix]);
+            if excess > 0 {
+                for key in &dropped[page_ix] {
+                    violations.push(Violation::DroppedWithHandle {
+                        key: *key,
+                        handles: excess,
+                    });
+                }
+            }
+            // The scheduler and each handle hold one reference to the page.
+            let refcount: u64 = page.refcount_get();
+            if refcount < handles + 1 {
+                violations.push(Violation::RefcountBelowHandles {
+                    page_ix,
+                    refcount,
+                    handles,
+                });
+            }
+        }
+        violations
+    }
+
+    /// Creates a handle for the task identified by `key`.
+    fn handle(&self, key: u64) -> SchedulerHandle {
+        let (page, _): (&WakerPageRef, usize) = self.get_page(key);
+        SchedulerHandle::with_stats(key, page.clone(), self.tasks[key as usize].stats.clone())
+    }
+
+    /// Flags the task identified by `key` as completed. Tasks that join it are to be woken up.
+    fn complete(&mut self, key: u64) {
+        let (page, subpage_ix): (&WakerPageRef, usize) = self.get_page(key);
+        page.mark_completed(subpage_ix);
+        if self.sequence.len() <= key as usize {
+            self.sequence.resize(key as usize + 1, 0);
+        }
+        self.sequence[key as usize] = self.completions;
+        self.completions += 1;
+        if let Some(queue) = self.completion_queue.as_mut() {
+            queue.push(key);
+        }
+        if let Some(mut joiners) = self.joiners.remove(&key) {
+            self.woken.append(&mut joiners);
+        }
+    }
+
+    /// Insert a task into our scheduler returning a key that may be used to drive its status. The task is polled in
+    /// the next poll only if `notified` is set, and it becomes a child of `parent`, if any.
+    fn insert(&mut self, future: F, notified: bool, parent: Option<u64>, info: TaskInfo) -> Option<u64> {
         let key: usize = self.slab.insert(future)?;
+        // Slab keys are allocated in order, so metadata is mostly appended.
+        if key == self.tasks.len() {
+            self.tasks.push(info);
+        } else {
+            if self.tasks.len() < key {
+                self.tasks.resize_with(key + 1, TaskInfo::default);
+            }
+            self.tasks[key] = info;
+        }
 
         // Add a new page to hold this future's status if the current page is filled.
         while key >= self.pages.len() << WAKER_BIT_LENGTH_SHIFT {
             self.pages.push(WakerPageRef::default());
         }
         let (page, subpage_ix): (&WakerPageRef, usize) = self.get_page(key as u64);
-        page.initialize(subpage_ix);
+        if notified {
+            page.initialize(subpage_ix);
+        } else {
+            page.clear(subpage_ix);
+        }
+        self.tree.insert(key as u64, parent);
         Some(key as u64)
     }
-}
 
-/// Associate Functions for Scheduler
-impl Scheduler {
-    /// Given a handle representing a future, remove the future from the scheduler returning it.
-    pub fn take(&self, mut handle: SchedulerHandle) -> Box<dyn SchedulerFuture> {
-        let mut inner: RefMut<Inner<Box<dyn SchedulerFuture>>> = self.inner.borrow_mut();
-        let key: u64 = handle.take_key().unwrap();
-        let (page, subpage_ix): (&WakerPageRef, usize) = inner.get_page(key);
-        assert!(!page.was_dropped(subpage_ix));
-        page.clear(subpage_ix);
-        inner.slab.remove_unpin(key as usize).unwrap()
+    /// Notifies the task identified by `key`. If there is no such task, `false` is returned.
+    fn notify(&self, key: u64) -> bool {
+        if self.slab.get(key as usize).is_none() {
+            return false;
+        }
+        let (page, subpage_ix): (&WakerPageRef, usize) = self.get_page(key);
+        page.notify(subpage_ix);
+        true
     }
 
-    /// Given the raw `key` representing this future return a proper handle.
-    pub fn from_raw_handle(&self, key: u64) -> Option<SchedulerHandle> {
-        let inner: Ref<Inner<Box<dyn SchedulerFuture>>> = self.inner.borrow();
-        inner.slab.get(key as usize)?;
-        let (page, _): (&WakerPageRef, usize) = inner.get_page(key);
-        let handle: SchedulerHandle = SchedulerHandle::new(key, page.clone());
-        Some(handle)
+    /// Computes flags for tasks of the `page_ix` page that are stored in the scheduler and have neither completed nor
+    /// been dropped.
+    fn live_tasks(&self, page_ix: usize) -> u64 {
+        let page: &WakerPageRef = &self.pages[page_ix];
+        (0..WAKER_BIT_LENGTH)
+            .filter(|subpage_ix| {
+                let ix: usize = (page_ix << WAKER_BIT_LENGTH_SHIFT) + subpage_ix;
+                self.slab.get(ix).is_some() && !page.has_completed(*subpage_ix) && !page.was_dropped(*subpage_ix)
+            })
+            .fold(0, |live, subpage_ix| live | (1 << subpage_ix))
     }
 
-    /// Insert a new task into our scheduler returning a handle corresponding to it.
-    pub fn insert<F: SchedulerFuture>(&self, future: F) -> Option<SchedulerHandle> {
-        let mut inner: RefMut<Inner<Box<dyn SchedulerFuture>>> = self.inner.borrow_mut();
-        let key: u64 = inner.insert(Box::new(future))?;
-        let (page, _): (&WakerPageRef, usize) = inner.get_page(key);
-        Some(SchedulerHandle::new(key, page.clone()))
+    /// Applies chaos, if enabled, to the `notified` flags of the `page_ix` page. Tasks flagged in `dropped` are not
+    /// woken up spuriously.
+    fn perturb(&mut self, page_ix: usize, notified: u64, dropped: u64) -> u64 {
+        if self.chaos.is_none() {
+            return notified;
+        }
+        let live: u64 = self.live_tasks(page_ix) & !dropped;
+        let round: u64 = self.round;
+        match self.chaos.as_mut() {
+            Some(chaos) => chaos.perturb(round, page_ix, notified, live),
+            None => notified,
+        }
     }
 
-    /// Poll all futures which are ready to run again. Tasks in our scheduler are notified when
-    /// relevant data or events happen. The relevant event have callback function (the waker) which
-    /// they can invoke to notify the scheduler that future should be polled again.
-    pub fn poll(&self) {
-        let mut inner: RefMut<Inner<Box<dyn SchedulerFuture>>> = self.inner.borrow_mut();
+    /// Returns a thread-safe waker for the task identified by `key`, if cross-thread wakeups are enabled.
+    #[cfg(target_os = "linux")]
+    fn remote_waker(&mut self, key: u64) -> Option<Waker> {
+        self.remote.as_mut().map(|remote| remote.waker(key))
+    }
 
-        // Iterate through pages.
-        for page_ix in 0..inner.pages.len() {
-            let (notified, dropped): (u64, u64) = {
-                let page: &mut WakerPageRef = &mut inner.pages[page_ix];
-                (page.take_notified(), page.take_dropped())
-            };
-            // There is some notified task in this page, so iterate through it.
-            if notified != 0 {
-                for subpage_ix in BitIter::from(notified) {
-                    // Handle notified tasks only.
-                    // Get future using our page indices and poll it!
-                    let ix: usize = (page_ix << WAKER_BIT_LENGTH_SHIFT) + subpage_ix;
-                    let waker: Waker = unsafe {
-                        let raw_waker: NonNull<u8> = inner.pages[page_ix].into_raw_waker_ref(subpage_ix);
-                        Waker::from_raw(WakerRef::new(raw_waker).into())
-                    };
-                    let mut sub_ctx: Context = Context::from_waker(&waker);
-
-                    let pinned_ref: Pin<&mut Box<dyn SchedulerFuture>> = inner.slab.get_pin_mut(ix).unwrap();
-                    let pinned_ptr = unsafe { Pin::into_inner_unchecked(pinned_ref) as *mut _ };
-
-                    // Poll future.
-                    drop(inner);
-                    let pinned_ref = unsafe { Pin::new_unchecked(&mut *pinned_ptr) };
-                    let poll_result: Poll<()> = Future::poll(pinned_ref, &mut sub_ctx);
-                    inner = self.inner.borrow_mut();
-
-                    match poll_result {
-                        Poll::Ready(()) => inner.pages[page_ix].mark_completed(subpage_ix),
-                        Poll::Pending => (),
-                    }
-                }
+    /// Returns a thread-safe waker for the task identified by `key`, if cross-thread wakeups are enabled.
+    #[cfg(not(target_os = "linux"))]
+    fn remote_waker(&mut self, _key: u64) -> Option<Waker> {
+        None
+    }
+
+    /// Notifies tasks that were woken up from other threads.
+    #[cfg(target_os = "linux")]
+    fn drain_remote(&mut self) {
+        let keys: Vec<u64> = match self.remote.as_ref() {
+            Some(remote) => remote.drain(),
+            None => return,
+        };
+        for key in keys {
+            // The task may have been removed in the meantime.
+            self.notify(key);
+        }
+    }
+}
+
+/// Associate Functions for Inner
+impl Inner<Box<dyn SchedulerFuture>> {
+    /// Cancels the tasks identified by `keys` that have not completed yet: their futures are replaced and they are
+    /// flagged as completed. The futures are dropped once the borrow is released. The future of the task that is being
+    /// polled is only replaced once its poll returns.
+    fn cancel_tasks(&mut self, keys: &[u64]) {
+        for key in keys {
+            let (page, subpage_ix): (&WakerPageRef, usize) = self.get_page(*key);
+            if page.has_completed(subpage_ix) {
+                continue;
             }
-            // There is some dropped task in this page, so iterate through it.
-            if dropped != 0 {
-                // Handle dropped tasks only.
-                for subpage_ix in BitIter::from(dropped) {
-                    if subpage_ix != 0 {
-                        let ix: usize = (page_ix << WAKER_BIT_LENGTH_SHIFT) + subpage_ix;
-                        inner.slab.remove(ix);
-                        inner.pages[page_ix].clear(subpage_ix);
-                    }
-                }
+            self.complete(*key);
+            if self.current == Some(*key) {
+                self.current_cancelled = true;
+                continue;
+            }
+            if let Some(future) = self.slab.get_pin_mut(*key as usize) {
+                let future: Box<dyn SchedulerFuture> = mem::replace(Pin::into_inner(future), Box::new(Cancelled));
+                self.cancelled.push(future);
             }
         }
     }
-}
 
-//==============================================================================
-// Trait Implementations
-//==============================================================================
+    /// Forgets the task identified by `key`, which was removed from the slab. It is removed from the tree, cancelling
+    /// all of its descendants.
+    fn remove_task(&mut self, key: u64) {
+        let children: Vec<u64> = self.tree.remove(key);
+        let descendants: Vec<u64> = children.iter().flat_map(|child| self.tree.subtree(*child)).collect();
+        self.cancel_tasks(&descendants);
+        self.joiners.remove(&key);
+        if let Some(slow_polls) = self.slow_polls.as_mut() {
+            slow_polls.remove(key);
+        }
+    }
 
-/// Default Trait Implementation for Scheduler
-impl Default for Scheduler {
-    /// Creates a scheduler with default values.
-    fn default() -> Self {
+    /// Removes tasks of the `page_ix` page whose handles were dropped, as flagged in `dropped`. Their descendants are
+    /// cancelled.
+    fn remove_dropped(&mut self, page_ix: usize, dropped: u64) {
+        for subpage_ix in BitIter::from(dropped) {
+            let ix: usize = (page_ix << WAKER_BIT_LENGTH_SHIFT) + subpage_ix;
+            self.slab.remove(ix);
+            self.pages[page_ix].clear(subpage_ix);
+            self.remove_task(ix as u64);
+        }
+    }
+}
+
+/// Associate Functions for Scheduler
+impl Scheduler {
+    /// Creates a scheduler that reads time from `clock`.
+    pub fn new(clock: Rc<dyn Clock>) -> Self {
         let inner: Inner<Box<dyn SchedulerFuture>> = Inner {
             slab: PinSlab::new(),
             pages: vec![],
+            tasks: Vec::new(),
+            round: 0,
+            simulation: None,
+            chaos: None,
+            recording: None,
+            replay: None,
+            idle_stats: IdleStats::default(),
+            pollers: Pollers::default(),
+            tree: TaskTree::default(),
+            current: None,
+            current_cancelled: false,
+            cancelled: Vec::new(),
+            joiners: HashMap::new(),
+            woken: Vec::new(),
+            completions: 0,
+            sequence: Vec::new(),
+            completion_queue: None,
+            coop_budget: None,
+            slow_polls: None,
+            tag_stats: None,
+            leak_report: None,
+            #[cfg(target_os = "linux")]
+            remote: None,
         };
         Self {
             inner: Rc::new(RefCell::new(inner)),
+            timer: Timer::new(clock),
         }
     }
-}
 
-//==============================================================================
-// Unit Tests
-//==============================================================================
+    /// Returns the current time, as seen by the clock of the target [Scheduler].
+    pub fn now(&self) -> Instant {
+        self.timer.now()
+    }
 
-#[cfg(test)]
-mod tests {
-    use crate::{
-        Scheduler,
-        SchedulerFuture,
-        SchedulerHandle,
-    };
-    use ::std::{
-        any::Any,
-        future::Future,
-        pin::Pin,
-        task::{
-            Context,
-            Poll,
-            Waker,
-        },
-    };
-    use ::test::{
-        black_box,
-        Bencher,
-    };
+    /// Returns the [Timer] that is driven by the target [Scheduler].
+    pub fn timer(&self) -> Timer {
+        self.timer.clone()
+    }
 
-    #[derive(Default)]
-    struct DummyFuture {
-        pub val: usize,
+    /// Returns a future that completes once `duration` has elapsed.
+    pub fn sleep(&self, duration: Duration) -> Sleep {
+        self.timer.sleep(duration)
     }
 
-    impl DummyFuture {
-        pub fn new(val: usize) -> Self {
-            let f: Self = Self { val };
-            f
-        }
+    /// Returns a future that completes once `deadline` is reached.
+    pub fn sleep_until(&self, deadline: Instant) -> Sleep {
+        self.timer.sleep_until(deadline)
     }
 
-    impl Future for DummyFuture {
-        type Output = ();
+    /// Runs `future` for at most `duration`.
+    pub fn timeout<F: Future>(&self, future: F, duration: Duration) -> Timeout<F> {
+        self.timer.timeout(future, duration)
+    }
 
-        fn poll(self: Pin<&mut Self>, ctx: &mut Context) -> Poll<Self::Output> {
-            match self.as_ref().val & 1 {
-                0 => Poll::Ready(()),
-                _ => {
-                    self.get_mut().val += 1;
-                    let waker: &Waker = ctx.waker();
-                    waker.wake_by_ref();
-                    Poll::Pending
-                },
-            }
+    /// Returns a stream of ticks that are `period` apart.
+    pub fn interval(&self, period: Duration) -> Interval {
+        self.timer.interval(period)
+    }
+
+    /// Given a handle representing a future, remove the future from the scheduler returning it.
+    /// Descendants of the future are cancelled. A task may not take itself out while it is polled, see
+    /// [Scheduler::cancel] instead.
+    pub fn take(&self, mut handle: SchedulerHandle) -> Box<dyn SchedulerFuture> {
+        let future: Box<dyn SchedulerFuture> = {
+            let mut inner: RefMut<Inner<Box<dyn SchedulerFuture>>> = self.inner.borrow_mut();
+            let key: u64 = handle.take_key().unwrap();
+            assert_ne!(inner.current, Some(key), "cannot take a task while it is polled");
+            let (page, subpage_ix): (&WakerPageRef, usize) = inner.get_page(key);
+            assert!(!page.was_dropped(subpage_ix));
+            page.clear(subpage_ix);
+            inner.remove_task(key);
+            inner.slab.remove_unpin(key as usize).unwrap()
+        };
+        self.release();
+        future
+    }
+
+    /// Cancels the task of `handle` and all of its descendants. Their futures are dropped, and they are flagged as
+    /// completed. The task is removed once `handle` is dropped. A task may cancel itself, or some ancestor, while it is
+    /// polled: it is flagged as completed right away, and its future is dropped once its poll returns.
+    pub fn cancel(&self, handle: &SchedulerHandle) {
+        {
+            let mut inner: RefMut<Inner<Box<dyn SchedulerFuture>>> = self.inner.borrow_mut();
+            let keys: Vec<u64> = inner.tree.subtree(handle.key());
+            inner.cancel_tasks(&keys);
         }
+        self.release();
     }
 
-    impl SchedulerFuture for DummyFuture {
-        fn as_any(self: Box<Self>) -> Box<dyn Any> {
-            self
+    /// Returns a future that completes once the task of `handle` completes or is cancelled. The task that awaits it is
+    /// woken up then, rather than polling [SchedulerHandle::has_completed].
+    pub fn join<'a>(&self, handle: &'a SchedulerHandle) -> Join<'a> {
+        Join::new(self.clone(), handle)
+    }
+
+    /// Registers `waker` to be woken up once the task identified by `key` completes.
+    pub(crate) fn add_joiner(&self, key: u64, waker: &Waker) {
+        let mut inner: RefMut<Inner<Box<dyn SchedulerFuture>>> = self.inner.borrow_mut();
+        let joiners: &mut Vec<Waker> = inner.joiners.entry(key).or_default();
+        if !joiners.iter().any(|joiner| joiner.will_wake(waker)) {
+            joiners.push(waker.clone());
         }
+    }
 
-        fn get_future(&self) -> &dyn Future<Output = ()> {
-            todo!()
+    /// Enables the completion queue of the target [Scheduler]. From then on, keys of tasks that complete or are
+    /// cancelled are queued up, until they are drained with [Scheduler::drain_completed].
+    pub fn enable_completion_queue(&self) {
+        let mut inner: RefMut<Inner<Box<dyn SchedulerFuture>>> = self.inner.borrow_mut();
+        if inner.completion_queue.is_none() {
+            inner.completion_queue = Some(Vec::new());
         }
     }
 
-    #[bench]
-    fn bench_scheduler_insert(b: &mut Bencher) {
-        let scheduler: Scheduler = Scheduler::default();
+    /// Takes out the keys of the tasks that completed since the last call, in completion order. A key may have been
+    /// reused by a new task if the handle of the completed one was dropped in the meantime.
+    pub fn drain_completed(&self) -> Vec<u64> {
+        let mut inner: RefMut<Inner<Box<dyn SchedulerFuture>>> = self.inner.borrow_mut();
+        let queue: &mut Vec<u64> = inner
+            .completion_queue
+            .as_mut()
+            .expect("completion queue should be enabled");
+        mem::take(queue)
+    }
 
-        b.iter(|| {
-            let future: DummyFuture = black_box(DummyFuture::default());
-            let handle: SchedulerHandle = scheduler.insert(future).expect("couldn't insert future in scheduler");
-            black_box(handle);
-        });
+    /// Returns the completed flags of the tasks in the `page_ix` page.
+    pub(crate) fn completed_in_page(&self, page_ix: usize) -> u64 {
+        self.inner.borrow().pages[page_ix].get_completed()
     }
 
-    #[test]
-    fn scheduler_poll_once() {
-        let scheduler: Scheduler = Scheduler::default();
+    /// Returns the sequence number in which the task identified by `key` completed. The task should have completed.
+    pub(crate) fn completion_sequence(&self, key: u64) -> u64 {
+        self.inner.borrow().sequence[key as usize]
+    }
 
-        // Insert a single future in the scheduler. This future shall complete
-        // with a single pool operation.
-        let future: DummyFuture = DummyFuture::new(0);
-        let handle: SchedulerHandle = match scheduler.insert(future) {
-            Some(handle) => handle,
-            None => panic!("insert() failed"),
+    /// Drops futures of cancelled tasks and wakes up tasks that join completed ones, outside of the borrow of the
+    /// target [Scheduler].
+    fn release(&self) {
+        let (cancelled, woken): (Vec<Box<dyn SchedulerFuture>>, Vec<Waker>) = {
+            let mut inner: RefMut<Inner<Box<dyn SchedulerFuture>>> = self.inner.borrow_mut();
+            (mem::take(&mut inner.cancelled), mem::take(&mut inner.woken))
         };
+        drop(cancelled);
+        woken.into_iter().for_each(Waker::wake);
 
-        // All futures are inserted in the scheduler with notification flag set.
-        // By polling once, our future should complete.
-        scheduler.poll();
+        // Report slow polls.
+        let pending: Option<(SlowPollFn, Vec<SlowPoll>)> = self
+            .inner
+            .borrow_mut()
+            .slow_polls
+            .as_mut()
+            .and_then(SlowPolls::take_pending);
+        if let Some((mut callback, polls)) = pending {
+            polls.iter().for_each(&mut callback);
+            if let Some(slow_polls) = self.inner.borrow_mut().slow_polls.as_mut() {
+                slow_polls.restore(callback);
+            }
+        }
+    }
 
-        assert_eq!(handle.has_completed(), true);
+    /// Returns the key of the parent of the task identified by `key`, if any.
+    pub fn parent(&self, key: u64) -> Option<u64> {
+        self.inner.borrow().tree.parent(key)
     }
 
-    #[test]
-    fn scheduler_poll_twice() {
-        let scheduler: Scheduler = Scheduler::default();
+    /// Returns a snapshot of the tree of tasks in the target [Scheduler], for debugging. Each root of the tree is a
+    /// task that was not inserted from within another task, or whose parent is gone.
+    pub fn task_tree(&self) -> Vec<TaskNode> {
+        let inner: Ref<Inner<Box<dyn SchedulerFuture>>> = self.inner.borrow();
+        let describe = |key: u64| inner.describe(key);
+        let mut roots: Vec<TaskNode> = Vec::new();
+        for key in 0..(inner.pages.len() << WAKER_BIT_LENGTH_SHIFT) as u64 {
+            if inner.slab.get(key as usize).is_some() && inner.tree.parent(key).is_none() {
+                roots.push(inner.tree.snapshot(key, &describe));
+            }
+        }
+        roots
+    }
 
-        // Insert a single future in the scheduler. This future shall complete
-        // with two poll operations.
-        let future: DummyFuture = DummyFuture::new(1);
-        let handle: SchedulerHandle = match scheduler.insert(future) {
-            Some(handle) => handle,
-            None => panic!("insert() failed"),
+    /// Given the raw `key` representing this future return a proper handle.
+    pub fn from_raw_handle(&self, key: u64) -> Option<SchedulerHandle> {
+        let inner: Ref<Inner<Box<dyn SchedulerFuture>>> = self.inner.borrow();
+        inner.slab.get(key as usize)?;
+        let handle: SchedulerHandle = inner.handle(key);
+        Some(handle)
+    }
+
+    /// Insert a new task into our scheduler returning a handle corresponding to it. A task that is inserted while
+    /// another one is polled becomes a child of the latter, and thus is cancelled along with it, unless it is inserted
+    /// with [TaskAttributes::detached].
+    #[track_caller]
+    pub fn insert<F: SchedulerFuture>(&self, future: F) -> Option<SchedulerHandle> {
+        self.insert_notified(future, true, TaskAttributes::default(), Location::caller())
+    }
+
+    /// Inserts a new task with `attributes` into the target [Scheduler], returning a handle corresponding to it.
+    #[track_caller]
+    pub fn insert_with<F: SchedulerFuture>(&self, future: F, attributes: TaskAttributes) -> Option<SchedulerHandle> {
+        self.insert_notified(future, true, attributes, Location::caller())
+    }
+
+    /// Inserts a new task into the target [Scheduler] without notifying it, returning a handle corresponding to it.
+    /// The task is not polled until it is woken up through [Scheduler::wake] or [Scheduler::wake_raw], since it has not
+    /// handed out any waker yet.
+    #[track_caller]
+    pub fn insert_parked<F: SchedulerFuture>(&self, future: F) -> Option<SchedulerHandle> {
+        self.insert_notified(future, false, TaskAttributes::default(), Location::caller())
+    }
+
+    /// Inserts a new task into the target [Scheduler], notifying it if `notified` is set.
+    fn insert_notified<F: SchedulerFuture>(
+        &self,
+        future: F,
+        notified: bool,
+        attributes: TaskAttributes,
+        location: &'static Location<'static>,
+    ) -> Option<SchedulerHandle> {
+        let mut inner: RefMut<Inner<Box<dyn SchedulerFuture>>> = self.inner.borrow_mut();
+        // Tasks inserted while another one is being polled are children of the latter.
+        let parent: Option<u64> = if attributes.detached { None } else { inner.current };
+        let info: TaskInfo = TaskInfo {
+            name: attributes.name.map(Rc::from),
+            type_name: any::type_name::<F>(),
+            location: Some(location),
+            tag: attributes.tag.map(Rc::from),
+            stats: inner.tag_stats.as_ref().map(|_| Rc::default()),
         };
+        let key: u64 = inner.insert(Box::new(future), notified, parent, info)?;
+        Some(inner.handle(key))
+    }
 
-        // All futures are inserted in the scheduler with notification flag set.
-        // By polling once, this future should make a transition.
-        scheduler.poll();
+    /// Describes the task identified by `key`. If there is no such task, `None` is returned.
+    pub fn describe(&self, key: u64) -> Option<TaskDescription> {
+        let inner: Ref<Inner<Box<dyn SchedulerFuture>>> = self.inner.borrow();
+        inner.slab.get(key as usize)?;
+        Some(inner.describe(key))
+    }
 
-        assert_eq!(handle.has_completed(), false);
+    /// Describes all tasks in the target [Scheduler], by increasing key.
+    pub fn tasks(&self) -> Vec<TaskDescription> {
+        let inner: Ref<Inner<Box<dyn SchedulerFuture>>> = self.inner.borrow();
+        (0..inner.tasks.len() as u64)
+            .filter(|key| inner.slab.get(*key as usize).is_some())
+            .map(|key| inner.describe(key))
+            .collect()
+    }
 
-        // This shall make the future ready.
-        scheduler.poll();
+    /// Suspends the task of `handle`: it is not polled until it is resumed. Wakeups that it receives in the meantime
+    /// are delivered when it is resumed.
+    pub fn suspend(&self, handle: &SchedulerHandle) {
+        let inner: Ref<Inner<Box<dyn SchedulerFuture>>> = self.inner.borrow();
+        let (page, subpage_ix): (&WakerPageRef, usize) = inner.get_page(handle.key());
+        page.suspend(subpage_ix);
+    }
 
-        assert_eq!(handle.has_completed(), true);
+    /// Resumes the task of `handle`, which is polled in the next poll if it was woken up while suspended.
+    pub fn resume(&self, handle: &SchedulerHandle) {
+        let inner: Ref<Inner<Box<dyn SchedulerFuture>>> = self.inner.borrow();
+        let (page, subpage_ix): (&WakerPageRef, usize) = inner.get_page(handle.key());
+        page.resume(subpage_ix);
+    }
+
+    /// Queries whether or not the task of `handle` is suspended.
+    pub fn is_suspended(&self, handle: &SchedulerHandle) -> bool {
+        let inner: Ref<Inner<Box<dyn SchedulerFuture>>> = self.inner.borrow();
+        let (page, subpage_ix): (&WakerPageRef, usize) = inner.get_page(handle.key());
+        page.is_suspended(subpage_ix)
+    }
+
+    /// Wakes up the task of `handle`, as if its waker was woken.
+    pub fn wake(&self, handle: &SchedulerHandle) {
+        self.wake_raw(handle.key());
+    }
+
+    /// Wakes up the task identified by `key`, as if its waker was woken. If there is no such task, `false` is returned.
+    pub fn wake_raw(&self, key: u64) -> bool {
+        self.inner.borrow().notify(key)
+    }
+
+    /// Wakes up the tasks identified by `keys`, setting the notification flags of each page at once. Keys of tasks
+    /// that do not exist are ignored. The number of tasks that were woken up is returned.
+    pub fn wake_many<I: IntoIterator<Item = u64>>(&self, keys: I) -> usize {
+        let inner: Ref<Inner<Box<dyn SchedulerFuture>>> = self.inner.borrow();
+        let mut masks: HashMap<usize, u64> = HashMap::new();
+        let mut woken: usize = 0;
+        for key in keys {
+            if inner.slab.get(key as usize).is_none() {
+                continue;
+            }
+            let page_ix: usize = key as usize >> WAKER_BIT_LENGTH_SHIFT;
+            let subpage_ix: usize = key as usize & (WAKER_BIT_LENGTH - 1);
+            *masks.entry(page_ix).or_default() |= 1 << subpage_ix;
+            woken += 1;
+        }
+        for (page_ix, mask) in masks {
+            inner.pages[page_ix].notify_many(mask);
+        }
+        woken
+    }
+
+    /// Registers `poller` to run in `phase` of every call to [Scheduler::poll] on the target [Scheduler], regardless of
+    /// whether any task is notified. Pollers run without borrowing the scheduler, so they may use it freely. If a
+    /// poller polls the scheduler, pollers do not run in that nested poll.
+    pub fn register_poller<P: FnMut(&PollerContext) + 'static>(&self, phase: PollerPhase, poller: P) -> PollerId {
+        self.inner.borrow_mut().pollers.register(phase, Box::new(poller))
+    }
+
+    /// Unregisters the poller identified by `id` from the target [Scheduler]. If there is no such poller, `false` is
+    /// returned.
+    pub fn unregister_poller(&self, id: PollerId) -> bool {
+        self.inner.borrow_mut().pollers.unregister(id)
+    }
+
+    /// Queries whether or not some task in the target [Scheduler] is notified, and thus would be polled in the next
+    /// call to [Scheduler::poll]. Wakeups that are held back by chaos count as notified, since they are released in a
+    /// later poll.
+    pub fn has_notified(&self) -> bool {
+        let inner: Ref<Inner<Box<dyn SchedulerFuture>>> = self.inner.borrow();
+        if matches!(inner.chaos.as_ref(), Some(chaos) if chaos.has_delayed()) {
+            return true;
+        }
+        #[cfg(target_os = "linux")]
+        if matches!(inner.remote.as_ref(), Some(remote) if remote.has_pending()) {
+            return true;
+        }
+        inner.pages.iter().any(|page| page.has_notified())
+    }
+
+    /// Enables cross-thread wakeups in the target [Scheduler]. In this mode, tasks are polled with wakers that may be
+    /// sent to and woken from other threads, and the scheduler may park the current thread when it is idle (see
+    /// [Scheduler::park]).
+    #[cfg(target_os = "linux")]
+    pub fn enable_remote_wakeups(&self) -> io::Result<()> {
+        let mut inner: RefMut<Inner<Box<dyn SchedulerFuture>>> = self.inner.borrow_mut();
+        if inner.remote.is_none() {
+            inner.remote = Some(Remote::new()?);
+        }
+        Ok(())
+    }
+
+    /// Parks the current thread until some task is woken up from another thread, `timeout` elapses, or the next timer
+    /// expires, whatever happens first. If some task is already notified, this returns right away. If cross-thread
+    /// wakeups are not enabled (see [Scheduler::enable_remote_wakeups]), no task may be woken up while the thread is
+    /// parked, so it sleeps until the timeout or the next timer, and fails if there is neither. With a virtual clock,
+    /// such as [crate::ManualClock], the thread does not block: the clock is moved ahead by the time it would have
+    /// slept, and only wakeups that are already pending are taken.
+    #[cfg(target_os = "linux")]
+    pub fn park(&self, timeout: Option<Duration>) -> io::Result<()> {
+        if self.has_notified() {
+            return Ok(());
+        }
+        let timeout: Option<Duration> = self.timer.park_timeout(timeout);
+        let inner: Ref<Inner<Box<dyn SchedulerFuture>>> = self.inner.borrow();
+        match (inner.remote.as_ref(), timeout) {
+            (Some(remote), timeout) => remote.park(timeout),
+            (None, Some(timeout)) => {
+                ::std::thread::sleep(timeout);
+                Ok(())
+            },
+            (None, None) => Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "cannot park without remote wakeups, a timeout, or a timer",
+            )),
+        }
+    }
+
+    /// Enables the deterministic simulation mode in the target [Scheduler]. In this mode, the order in which notified
+    /// tasks are polled is permuted by a pseudo-random number generator that is initialized with `seed`. If a panic
+    /// unwinds through the scheduler, the seed is reported in the standard error.
+    pub fn enable_simulation(&self, seed: u64) {
+        self.inner.borrow_mut().simulation = Some(Simulation::new(seed));
+    }
+
+    /// Returns the seed of the simulation that is running in the target [Scheduler], if any.
+    pub fn simulation_seed(&self) -> Option<u64> {
+        self.inner.borrow().simulation.as_ref().map(Simulation::seed)
+    }
+
+    /// Enables the chaos mode in the target [Scheduler], as described by `config`. In this mode, the scheduler wakes
+    /// up idle tasks spuriously and delays wakeups by some poll rounds. If a panic unwinds through the scheduler, the
+    /// seed of the chaos is reported in the standard error.
+    pub fn enable_chaos(&self, config: ChaosConfig) {
+        self.inner.borrow_mut().chaos = Some(Chaos::new(config));
+    }
+
+    /// Returns the configuration of the chaos that is running in the target [Scheduler], if any.
+    pub fn chaos_config(&self) -> Option<ChaosConfig> {
+        self.inner.borrow().chaos.as_ref().map(|chaos| *chaos.config())
+    }
+
+    /// Starts recording scheduling decisions in the target [Scheduler]. A recording that is in progress is discarded.
+    pub fn start_recording(&self) {
+        let mut inner: RefMut<Inner<Box<dyn SchedulerFuture>>> = self.inner.borrow_mut();
+        let round: u64 = inner.round;
+        inner.recording = Some((Recording::default(), round));
+    }
+
+    /// Stops recording scheduling decisions in the target [Scheduler], returning what was recorded.
+    pub fn stop_recording(&self) -> Option<Recording> {
+        self.inner.borrow_mut().recording.take().map(|(recording, _)| recording)
+    }
+
+    /// Replays the scheduling decisions in `recording`. From the next poll round on, the target [Scheduler] polls
+    /// exactly the tasks that were recorded, in the same order, until the recording is over. If a task is missing or
+    /// its poll result differs from the recorded one, the replay diverged and the scheduler panics. Tasks that are
+    /// woken up but not polled by the recording are polled once the replay is over.
+    pub fn replay(&self, recording: Recording) {
+        let mut inner: RefMut<Inner<Box<dyn SchedulerFuture>>> = self.inner.borrow_mut();
+        let round: u64 = inner.round;
+        inner.replay = Some(Replay::new(recording, round));
+    }
+
+    /// Queries whether or not the target [Scheduler] is replaying a recording.
+    pub fn is_replaying(&self) -> bool {
+        self.inner.borrow().replay.is_some()
+    }
+
+    /// Runs the target [Scheduler] until `done` returns true, which is checked before every poll and before idling. In
+    /// iterations where no task is left notified after polling, the loop idles as dictated by `policy`. Parking
+    /// policies are cut short by tasks that are woken up from other threads only if cross-thread wakeups are enabled
+    /// (see [Scheduler::park]).
+    pub fn run(&self, policy: IdlePolicy, mut done: impl FnMut() -> bool) -> io::Result<()> {
+        let mut idle: u32 = 0;
+        while !done() {
+            self.poll();
+            let action: Option<IdleAction> = if self.has_notified() || done() {
+                idle = 0;
+                None
+            } else {
+                idle = idle.saturating_add(1);
+                Some(policy.action(idle))
+            };
+            self.inner.borrow_mut().idle_stats.record(action);
+            match action {
+                None => (),
+                #[cfg(target_os = "linux")]
+                Some(IdleAction::Park(timeout)) => self.park(Some(timeout))?,
+                Some(action) => action.relax(),
+            }
+        }
+        Ok(())
+    }
+
+    /// Returns statistics of the run loop of the target [Scheduler].
+    pub fn idle_stats(&self) -> IdleStats {
+        self.inner.borrow().idle_stats
+    }
+
+    /// Poll all futures which are ready to run again. Tasks in our scheduler are notified when
+    /// relevant data or events happen. The relevant event have callback function (the waker) which
+    /// they can invoke to notify the scheduler that future should be polled again.
+    pub fn poll(&self) {
+        self.run_pollers(PollerPhase::BeforePoll);
+        self.poll_tasks();
+        self.release();
+        self.run_pollers(PollerPhase::AfterPoll);
+    }
+
+    /// Sets the cooperative budget that tasks of the target [Scheduler] get in each poll. With `None`, which is the
+    /// default, tasks are not constrained. See [crate::consume_budget].
+    pub fn set_coop_budget(&self, budget: Option<u32>) {
+        self.inner.borrow_mut().coop_budget = budget;
+    }
+
+    /// Enables slow-poll detection in the target [Scheduler]. From then on, every poll of a task is timed: polls that
+    /// take at least `threshold` are reported to `callback`, which runs without borrowing the scheduler, and the
+    /// `capacity` slowest tasks are kept.
+    pub fn enable_slow_polls<C: FnMut(&SlowPoll) + 'static>(&self, threshold: Duration, capacity: usize, callback: C) {
+        self.inner.borrow_mut().slow_polls = Some(SlowPolls::new(threshold, capacity, Box::new(callback)));
+    }
+
+    /// Returns the slowest poll of each of the slowest tasks in the target [Scheduler], slowest first. If slow-poll
+    /// detection is not enabled, the list is empty.
+    pub fn slowest_polls(&self) -> Vec<SlowPoll> {
+        match self.inner.borrow().slow_polls.as_ref() {
+            Some(slow_polls) => slow_polls.slowest().to_vec(),
+            None => Vec::new(),
+        }
+    }
+
+    /// Enables accounting of the time that tasks of the target [Scheduler] spend being polled. Statistics are then
+    /// available through [SchedulerHandle::stats], [Scheduler::task_stats] and [Scheduler::stats_by_tag]. Only tasks
+    /// that are inserted from then on are accounted.
+    pub fn enable_task_stats(&self) {
+        let mut inner: RefMut<Inner<Box<dyn SchedulerFuture>>> = self.inner.borrow_mut();
+        if inner.tag_stats.is_none() {
+            inner.tag_stats = Some(HashMap::new());
+        }
+    }
+
+    /// Tags the task of `handle` with `tag`. From then on, its statistics are also accounted for `tag`.
+    pub fn set_tag(&self, handle: &SchedulerHandle, tag: &str) {
+        self.inner.borrow_mut().tasks[handle.key() as usize].tag = Some(Rc::from(tag));
+    }
+
+    /// Returns the keys and statistics of the tasks in the target [Scheduler], by increasing key.
+    pub fn task_stats(&self) -> Vec<(u64, TaskStats)> {
+        let inner: Ref<Inner<Box<dyn SchedulerFuture>>> = self.inner.borrow();
+        inner
+            .tasks
+            .iter()
+            .enumerate()
+            .filter(|(key, _)| inner.slab.get(*key).is_some())
+            .map(|(key, info)| {
+                let stats: TaskStats = info.stats.as_ref().map_or_else(TaskStats::default, |stats| stats.get());
+                (key as u64, stats)
+            })
+            .collect()
+    }
+
+    /// Returns statistics of tagged tasks in the target [Scheduler], by tag, including tasks that are gone. If
+    /// accounting is not enabled, the map is empty.
+    pub fn stats_by_tag(&self) -> HashMap<Rc<str>, TaskStats> {
+        self.inner.borrow().tag_stats.clone().unwrap_or_default()
+    }
+
+    /// Enables the leak report of the target [Scheduler]. When the last reference to the scheduler is dropped, tasks
+    /// that are still stored in it and pages that are still referenced from outside of it are reported to `callback`,
+    /// if there is any.
+    pub fn enable_leak_report<C: FnOnce(&LeakReport) + 'static>(&self, callback: C) {
+        self.inner.borrow_mut().leak_report = Some(Box::new(callback));
+    }
+
+    /// Cross-checks the internal state of the target [Scheduler], returning violations of its invariants. This is
+    /// meant for debugging, and may be run in tests after every operation on the scheduler.
+    pub fn validate(&self) -> Vec<Violation> {
+        self.inner.borrow().validate()
+    }
+
+    /// Polls the target [Scheduler] repeatedly, until no task is notified or `max_rounds` polls have run, so that tasks
+    /// woken up in a poll are polled right away. The number of polls that ran is returned.
+    pub fn poll_until_quiescent(&self, max_rounds: usize) -> usize {
+        for round in 0..max_rounds {
+            self.poll();
+            if !self.has_notified() {
+                return round + 1;
+            }
+        }
+        max_rounds
+    }
+
+    /// Runs the pollers that are registered for `phase`.
+    fn run_pollers(&self, phase: PollerPhase) {
+        let pollers: Vec<(PollerId, PollerFn)> = {
+            let mut inner: RefMut<Inner<Box<dyn SchedulerFuture>>> = self.inner.borrow_mut();
+            // Pollers do not run in a poll that is nested in one of them.
+            if inner.pollers.is_empty() || inner.pollers.is_running() {
+                return;
+            }
+            inner.pollers.take(phase)
+        };
+        let mut restore: RestorePollers = RestorePollers {
+            scheduler: self,
+            phase,
+            pollers,
+        };
+        let ctx: PollerContext = PollerContext::new(self);
+        for (_, poller) in restore.pollers.iter_mut() {
+            poller(&ctx);
+        }
+    }
+
+    /// Polls all notified tasks.
+    fn poll_tasks(&self) {
+        // Fire expired timers first, so that tasks waiting on them are polled right away.
+        self.timer.advance();
+
+        let mut inner: RefMut<Inner<Box<dyn SchedulerFuture>>> = self.inner.borrow_mut();
+        #[cfg(target_os = "linux")]
+        inner.drain_remote();
+        inner.round += 1;
+        let _reporters: (Option<SeedReporter>, Option<SeedReporter>) = (
+            inner.simulation.as_ref().map(Simulation::reporter),
+            inner.chaos.as_ref().map(Chaos::reporter),
+        );
+
+        if inner.replay.is_some() {
+            self.poll_replay(inner);
+            return;
+        }
+        if inner.simulation.is_some() {
+            self.poll_simulation(inner);
+            return;
+        }
+
+        // Iterate through pages.
+        for page_ix in 0..inner.pages.len() {
+            let (notified, dropped): (u64, u64) = {
+                let page: &mut WakerPageRef = &mut inner.pages[page_ix];
+                (page.take_notified(), page.take_dropped())
+            };
+            let notified: u64 = inner.perturb(page_ix, notified, dropped);
+            // There is some notified task in this page, so iterate through it.
+            if notified != 0 {
+                for subpage_ix in BitIter::from(notified) {
+                    // Handle notified tasks only.
+                    let key: u64 = ((page_ix << WAKER_BIT_LENGTH_SHIFT) + subpage_ix) as u64;
+                    inner = self.poll_task(inner, key);
+                }
+            }
+            // There is some dropped task in this page, so iterate through it.
+            if dropped != 0 {
+                inner.remove_dropped(page_ix, dropped);
+            }
+        }
+    }
+
+    /// Polls all notified tasks in the order that is dictated by the simulation of the target [Scheduler].
+    fn poll_simulation<'a>(
+        &'a self,
+        mut inner: RefMut<'a, Inner<Box<dyn SchedulerFuture>>>,
+    ) -> RefMut<'a, Inner<Box<dyn SchedulerFuture>>> {
+        let mut keys: Vec<u64> = Vec::new();
+        let mut dropped: Vec<u64> = Vec::with_capacity(inner.pages.len());
+        for page_ix in 0..inner.pages.len() {
+            let page: &WakerPageRef = &inner.pages[page_ix];
+            let (notified, page_dropped): (u64, u64) = (page.take_notified(), page.take_dropped());
+            let notified: u64 = inner.perturb(page_ix, notified, page_dropped);
+            keys.extend(
+                BitIter::from(notified).map(|subpage_ix| ((page_ix << WAKER_BIT_LENGTH_SHIFT) + subpage_ix) as u64),
+            );
+            dropped.push(page_dropped);
+        }
+        let simulation: &mut Simulation = inner.simulation.as_mut().expect("simulation should be enabled");
+        simulation.shuffle(&mut keys);
+
+        for key in keys {
+            inner = self.poll_task(inner, key);
+        }
+        for (page_ix, dropped) in dropped.into_iter().enumerate() {
+            if dropped != 0 {
+                inner.remove_dropped(page_ix, dropped);
+            }
+        }
+        inner
+    }
+
+    /// Polls the tasks that were recorded for the current poll round, in the order in which they were recorded.
+    /// Notifications of tasks that are not polled in this round are kept, so that they are delivered once the replay
+    /// is over.
+    fn poll_replay<'a>(
+        &'a self,
+        mut inner: RefMut<'a, Inner<Box<dyn SchedulerFuture>>>,
+    ) -> RefMut<'a, Inner<Box<dyn SchedulerFuture>>> {
+        let mut notified: Vec<u64> = Vec::with_capacity(inner.pages.len());
+        let mut dropped: Vec<u64> = Vec::with_capacity(inner.pages.len());
+        for page in &inner.pages {
+            notified.push(page.take_notified());
+            dropped.push(page.take_dropped());
+        }
+        let round: u64 = inner.round;
+        let replay: &mut Replay = inner.replay.as_mut().expect("replay should be in progress");
+        let decisions: Vec<Decision> = replay.take_round(round);
+        if replay.is_done() {
+            inner.replay = None;
+        }
+
+        for decision in decisions {
+            let present: bool = inner.slab.get(decision.key as usize).is_some();
+            assert!(
+                present,
+                "replay diverged: task {} is missing in round {}",
+                decision.key, decision.round
+            );
+            inner = self.poll_task(inner, decision.key);
+            let (page, subpage_ix): (&WakerPageRef, usize) = inner.get_page(decision.key);
+            let ready: bool = page.has_completed(subpage_ix);
+            assert_eq!(
+                ready, decision.ready,
+                "replay diverged: task {} has a different poll result in round {}",
+                decision.key, decision.round
+            );
+            let (page_ix, subpage_ix): (usize, usize) = (
+                decision.key as usize >> WAKER_BIT_LENGTH_SHIFT,
+                decision.key as usize & (WAKER_BIT_LENGTH - 1),
+            );
+            notified[page_ix] &= !(1 << subpage_ix);
+        }
+        for (page, notified) in inner.pages.iter().zip(notified) {
+            page.notify_many(notified);
+        }
+        for (page_ix, dropped) in dropped.into_iter().enumerate() {
+            if dropped != 0 {
+                inner.remove_dropped(page_ix, dropped);
+            }
+        }
+        inner
+    }
+
+    /// Polls the task identified by `key`. The borrow of the scheduler is released while the task runs.
+    fn poll_task<'a>(
+        &'a self,
+        mut inner: RefMut<'a, Inner<Box<dyn SchedulerFuture>>>,
+        key: u64,
+    ) -> RefMut<'a, Inner<Box<dyn SchedulerFuture>>> {
+        // Skip tasks that were removed or cancelled since they were notified.
+        let (page, subpage_ix): (&WakerPageRef, usize) = inner.get_page(key);
+        if page.has_completed(subpage_ix) || inner.slab.get(key as usize).is_none() {
+            return inner;
+        }
+        // Hold the notification back for tasks that were suspended since they were notified.
+        if page.is_suspended(subpage_ix) {
+            page.notify(subpage_ix);
+            return inner;
+        }
+
+        // Get future using our page indices and poll it!
+        let waker: Waker = match inner.remote_waker(key) {
+            Some(waker) => waker,
+            None => {
+                let (page, subpage_ix): (&WakerPageRef, usize) = inner.get_page(key);
+                unsafe {
+                    let raw_waker: NonNull<u8> = page.into_raw_waker_ref(subpage_ix);
+                    Waker::from_raw(WakerRef::new(raw_waker).into())
+                }
+            },
+        };
+        let mut sub_ctx: Context = Context::from_waker(&waker);
+
+        let pinned_ref: Pin<&mut Box<dyn SchedulerFuture>> = inner.slab.get_pin_mut(key as usize).unwrap();
+        let pinned_ptr = unsafe { Pin::into_inner_unchecked(pinned_ref) as *mut _ };
+
+        // Poll future.
+        let parent: Option<u64> = inner.current.replace(key);
+        let parent_cancelled: bool = mem::take(&mut inner.current_cancelled);
+        let budget: Option<u32> = inner.coop_budget;
+        let timed: bool = inner.slow_polls.is_some() || inner.tag_stats.is_some();
+        // Polls are timed with the clock of the scheduler, so that they may be simulated.
+        let started: Option<Instant> = if timed { Some(self.timer.now()) } else { None };
+        drop(inner);
+        let pinned_ref = unsafe { Pin::new_unchecked(&mut *pinned_ptr) };
+        let poll_result: Poll<()> = coop::with_budget(budget, || Future::poll(pinned_ref, &mut sub_ctx));
+        let elapsed: Option<Duration> = started.map(|started| self.timer.now().saturating_duration_since(started));
+        let mut inner: RefMut<Inner<Box<dyn SchedulerFuture>>> = self.inner.borrow_mut();
+        inner.current = parent;
+        let cancelled: bool = mem::replace(&mut inner.current_cancelled, parent_cancelled);
+        if let Some(elapsed) = elapsed {
+            let inner_: &mut Inner<Box<dyn SchedulerFuture>> = &mut inner;
+            let info: &TaskInfo = &inner_.tasks[key as usize];
+            if let Some(slow_polls) = inner_.slow_polls.as_mut() {
+                slow_polls.record(SlowPoll {
+                    key,
+                    name: info.name.clone(),
+                    type_name: info.type_name,
+                    location: info.location,
+                    duration: elapsed,
+                });
+            }
+            if let Some(tag_stats) = inner_.tag_stats.as_mut() {
+                if let Some(stats_) = info.stats.as_ref() {
+                    let mut stats: TaskStats = stats_.get();
+                    stats.record(elapsed);
+                    stats_.set(stats);
+                }
+                if let Some(tag) = info.tag.as_ref() {
+                    tag_stats.entry(tag.clone()).or_default().record(elapsed);
+                }
+            }
+        }
+
+        if cancelled {
+            // The task was cancelled while it was polled, so its future may only be dropped now.
+            if let Some(future) = inner.slab.get_pin_mut(key as usize) {
+                let future: Box<dyn SchedulerFuture> = mem::replace(Pin::into_inner(future), Box::new(Cancelled));
+                inner.cancelled.push(future);
+            }
+        } else if poll_result.is_ready() {
+            inner.complete(key);
+        }
+        let round: u64 = inner.round;
+        if let Some((recording, base_round)) = inner.recording.as_mut() {
+            recording.push(Decision {
+                round: round - *base_round,
+                key,
+                ready: poll_result.is_ready(),
+            });
+        }
+        inner
+    }
+}
+
+//==============================================================================
+// Trait Implementations
+//==============================================================================
+
+/// Drop Trait Implementation for Inner
+impl<F: Future<Output = ()> + Unpin> Drop for Inner<F> {
+    /// Reports leaks, if enabled.
+    fn drop(&mut self) {
+        let callback: LeakReportFn = match self.leak_report.take() {
+            Some(callback) => callback,
+            None => return,
+        };
+        let tasks: Vec<TaskDescription> = (0..self.tasks.len() as u64)
+            .filter(|key| self.slab.get(*key as usize).is_some())
+            .map(|key| self.describe(key))
+            .collect();
+
+        // Drop tasks and wakers that are held by the scheduler, so that only references from outside of it remain.
+        drop(mem::take(&mut self.slab));
+        self.cancelled.clear();
+        self.joiners.clear();
+        self.woken.clear();
+        let pages: Vec<PageLeak> = self
+            .pages
+            .iter()
+            .enumerate()
+            .filter(|(_, page)| page.refcount_get() > 1)
+            .map(|(page_ix, page)| PageLeak {
+                page_ix,
+                refcount: page.refcount_get(),
+            })
+            .collect();
+
+        let report: LeakReport = LeakReport { tasks, pages };
+        if !report.is_empty() {
+            callback(&report);
+        }
+    }
+}
+
+/// Drop Trait Implementation for Poller Restorers
+impl<'a> Drop for RestorePollers<'a> {
+    fn drop(&mut self) {
+        let pollers: Vec<(PollerId, PollerFn)> = mem::take(&mut self.pollers);
+        self.scheduler.inner.borrow_mut().pollers.restore(self.phase, pollers);
+    }
+}
+
+/// Debug Trait Implementation for Scheduler
+impl fmt::Debug for Scheduler {
+    /// Writes the poll round and a description of every task in the target [Scheduler].
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let tasks: Vec<TaskDescription> = self.tasks();
+        f.debug_struct("Scheduler")
+            .field("round", &self.inner.borrow().round)
+            .field("tasks", &tasks)
+            .finish()
+    }
+}
+
+/// Default Trait Implementation for Scheduler
+impl Default for Scheduler {
+    /// Creates a scheduler with default values.
+    fn default() -> Self {
+        Self::new(Rc::new(SystemClock))
+    }
+}
+
+//==============================================================================
+// Unit Tests
+//==============================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        TaskFlag,
+        Violation,
+    };
+    use crate::{
+        test_utils::{
+            yield_now,
+            TestTask,
+        },
+        ChaosConfig,
+        ManualClock,
+        Recording,
+        Scheduler,
+        SchedulerFuture,
+        SchedulerHandle,
+        TaskAttributes,
+        TaskNode,
+        TaskStats,
+    };
+    use ::std::{
+        any::Any,
+        cell::{
+            Cell,
+            RefCell,
+        },
+        collections::HashMap,
+        future::{
+            self,
+            Future,
+        },
+        pin::Pin,
+        rc::Rc,
+        task::{
+            Context,
+            Poll,
+            Waker,
+        },
+        thread,
+        time::Duration,
+    };
+    use ::test::{
+        black_box,
+        Bencher,
+    };
+
+    #[derive(Default)]
+    struct DummyFuture {
+        pub val: usize,
+    }
+
+    impl DummyFuture {
+        pub fn new(val: usize) -> Self {
+            let f: Self = Self { val };
+            f
+        }
+    }
+
+    impl Future for DummyFuture {
+        type Output = ();
+
+        fn poll(self: Pin<&mut Self>, ctx: &mut Context) -> Poll<Self::Output> {
+            match self.as_ref().val & 1 {
+                0 => Poll::Ready(()),
+                _ => {
+                    self.get_mut().val += 1;
+                    let waker: &Waker = ctx.waker();
+                    waker.wake_by_ref();
+                    Poll::Pending
+                },
+            }
+        }
+    }
+
+    impl SchedulerFuture for DummyFuture {
+        fn as_any(self: Box<Self>) -> Box<dyn Any> {
+            self
+        }
+
+        fn get_future(&self) -> &dyn Future<Output = ()> {
+            todo!()
+        }
+    }
+
+    /// Future that never completes and counts how many times it was polled.
+    struct CountingFuture {
+        polls: Rc<Cell<usize>>,
+        /// Whether or not the future wakes itself up when polled.
+        wake: bool,
+    }
+
+    impl Future for CountingFuture {
+        type Output = ();
+
+        fn poll(self: Pin<&mut Self>, ctx: &mut Context) -> Poll<Self::Output> {
+            self.polls.set(self.polls.get() + 1);
+            if self.wake {
+                ctx.waker().wake_by_ref();
+            }
+            Poll::Pending
+        }
+    }
+
+    impl SchedulerFuture for CountingFuture {
+        fn as_any(self: Box<Self>) -> Box<dyn Any> {
+            self
+        }
+
+        fn get_future(&self) -> &dyn Future<Output = ()> {
+            self
+        }
+    }
+
+    #[bench]
+    fn bench_scheduler_insert(b: &mut Bencher) {
+        let scheduler: Scheduler = Scheduler::default();
+
+        b.iter(|| {
+            let future: DummyFuture = black_box(DummyFuture::default());
+            let handle: SchedulerHandle = scheduler.insert(future).expect("couldn't insert future in scheduler");
+            black_box(handle);
+        });
+    }
+
+    #[test]
+    fn scheduler_poll_once() {
+        let scheduler: Scheduler = Scheduler::default();
+
+        // Insert a single future in the scheduler. This future shall complete
+        // with a single pool operation.
+        let future: DummyFuture = DummyFuture::new(0);
+        let handle: SchedulerHandle = match scheduler.insert(future) {
+            Some(handle) => handle,
+            None => panic!("insert() failed"),
+        };
+
+        // All futures are inserted in the scheduler with notification flag set.
+        // By polling once, our future should complete.
+        scheduler.poll();
+
+        assert_eq!(handle.has_completed(), true);
+    }
+
+    #[test]
+    fn scheduler_poll_twice() {
+        let scheduler: Scheduler = Scheduler::default();
+
+        // Insert a single future in the scheduler. This future shall complete
+        // with two poll operations.
+        let future: DummyFuture = DummyFuture::new(1);
+        let handle: SchedulerHandle = match scheduler.insert(future) {
+            Some(handle) => handle,
+            None => panic!("insert() failed"),
+        };
+
+        // All futures are inserted in the scheduler with notification flag set.
+        // By polling once, this future should make a transition.
+        scheduler.poll();
+
+        assert_eq!(handle.has_completed(), false);
+
+        // This shall make the future ready.
+        scheduler.poll();
+
+        assert_eq!(handle.has_completed(), true);
+    }
+
+    #[test]
+    fn scheduler_manual_clock() {
+        let clock: Rc<ManualClock> = Rc::new(ManualClock::new());
+        let scheduler: Scheduler = Scheduler::new(clock.clone());
+        let fired: Rc<RefCell<Vec<u64>>> = Rc::new(RefCell::new(Vec::new()));
+
+        // Insert tasks in reverse order of their deadlines.
+        let mut handles: Vec<SchedulerHandle> = Vec::new();
+        for secs in [45, 20, 10, 1, 25] {
+            let scheduler_: Scheduler = scheduler.clone();
+            let fired_: Rc<RefCell<Vec<u64>>> = fired.clone();
+            let handle: SchedulerHandle = match scheduler.insert(TestTask::new(async move {
+                scheduler_.sleep(Duration::from_secs(secs)).await;
+                fired_.borrow_mut().push(secs);
+            })) {
+                Some(handle) => handle,
+                None => panic!("insert() failed"),
+            };
+            handles.push(handle);
+        }
+        scheduler.poll();
+        assert!(fired.borrow().is_empty());
+
+        // Jump 30 seconds ahead in small steps, so that we observe the order in which tasks fire.
+        for _ in 0..30 {
+            clock.advance(Duration::from_secs(1));
+            scheduler.poll();
+        }
+        assert_eq!(*fired.borrow(), vec![1, 10, 20, 25]);
+        assert_eq!(
+            scheduler.timer().next_deadline(),
+            Some(scheduler.now() + Duration::from_secs(15))
+        );
+
+        // Jump straight to the last deadline.
+        clock.advance(Duration::from_secs(15));
+        scheduler.poll();
+        assert_eq!(*fired.borrow(), vec![1, 10, 20, 25, 45]);
+        assert!(handles.iter().all(|handle| handle.has_completed()));
+    }
+
+    /// Runs 128 tasks in a simulation initialized with `seed`, returning the order in which they were polled.
+    fn simulate(seed: u64) -> Vec<usize> {
+        let scheduler: Scheduler = Scheduler::default();
+        scheduler.enable_simulation(seed);
+        assert_eq!(scheduler.simulation_seed(), Some(seed));
+
+        let order: Rc<RefCell<Vec<usize>>> = Rc::new(RefCell::new(Vec::new()));
+        let mut handles: Vec<SchedulerHandle> = Vec::new();
+        for i in 0..128 {
+            let order_: Rc<RefCell<Vec<usize>>> = order.clone();
+            let handle: SchedulerHandle = match scheduler.insert(TestTask::new(async move {
+                order_.borrow_mut().push(i);
+            })) {
+                Some(handle) => handle,
+                None => panic!("insert() failed"),
+            };
+            handles.push(handle);
+        }
+        scheduler.poll();
+        assert!(handles.iter().all(|handle| handle.has_completed()));
+
+        let order: Vec<usize> = order.borrow().clone();
+        order
+    }
+
+    #[test]
+    fn scheduler_simulation() {
+        let order: Vec<usize> = simulate(42);
+        assert_eq!(order.len(), 128);
+        assert_ne!(order, (0..128).collect::<Vec<usize>>());
+
+        // The same seed yields the same interleaving, across pages.
+        assert_eq!(simulate(42), order);
+        assert_ne!(simulate(43), order);
+    }
+
+    #[test]
+    fn scheduler_chaos_spurious_wakeups() {
+        let scheduler: Scheduler = Scheduler::default();
+        scheduler.enable_chaos(ChaosConfig {
+            seed: 1,
+            spurious_wakeup_probability: 1.0,
+            ..Default::default()
+        });
+
+        // An idle task gets polled in every round.
+        let polls: Rc<Cell<usize>> = Rc::new(Cell::new(0));
+        let future: CountingFuture = CountingFuture {
+            polls: polls.clone(),
+            wake: false,
+        };
+        let handle: SchedulerHandle = match scheduler.insert(future) {
+            Some(handle) => handle,
+            None => panic!("insert() failed"),
+        };
+        for _ in 0..10 {
+            scheduler.poll();
+        }
+        assert_eq!(polls.get(), 10);
+
+        // Completed tasks are never woken up.
+        let handle_: SchedulerHandle = match scheduler.insert(DummyFuture::new(0)) {
+            Some(handle) => handle,
+            None => panic!("insert() failed"),
+        };
+        scheduler.poll();
+        scheduler.poll();
+        assert!(handle_.has_completed());
+        drop(handle);
+    }
+
+    #[test]
+    fn scheduler_chaos_delayed_wakeups() {
+        let scheduler: Scheduler = Scheduler::default();
+        scheduler.enable_chaos(ChaosConfig {
+            seed: 2,
+            delay_probability: 1.0,
+            max_delay_rounds: 3,
+            ..Default::default()
+        });
+        assert_eq!(scheduler.chaos_config().map(|config| config.max_delay_rounds), Some(3));
+
+        // Every wakeup is delayed by one to three rounds.
+        let polls: Rc<Cell<usize>> = Rc::new(Cell::new(0));
+        let future: CountingFuture = CountingFuture {
+            polls: polls.clone(),
+            wake: true,
+        };
+        let counting: SchedulerHandle = match scheduler.insert(future) {
+            Some(handle) => handle,
+            None => panic!("insert() failed"),
+        };
+        for _ in 0..30 {
+            scheduler.poll();
+        }
+        assert!(polls.get() >= 7 && polls.get() <= 15);
+
+        // Futures that honor the contract still complete, and delayed wakeups keep the scheduler busy.
+        let handle: SchedulerHandle = match scheduler.insert(DummyFuture::new(1)) {
+            Some(handle) => handle,
+            None => panic!("insert() failed"),
+        };
+        drop(counting);
+        scheduler.poll_until_quiescent(16);
+        assert!(handle.has_completed());
+    }
+
+    /// Inserts 100 tasks that log their identifiers in `order` whenever they are polled.
+    fn insert_logging_tasks(scheduler: &Scheduler, order: &Rc<RefCell<Vec<usize>>>) -> Vec<SchedulerHandle> {
+        (0..100)
+            .map(|i| {
+                let order_: Rc<RefCell<Vec<usize>>> = order.clone();
+                match scheduler.insert(TestTask::new(async move {
+                    order_.borrow_mut().push(i);
+                    yield_now().await;
+                    order_.borrow_mut().push(i);
+                })) {
+                    Some(handle) => handle,
+                    None => panic!("insert() failed"),
+                }
+            })
+            .collect()
+    }
+
+    #[test]
+    fn scheduler_record_replay() {
+        // Record a simulated run.
+        let scheduler: Scheduler = Scheduler::default();
+        scheduler.enable_simulation(7);
+        let recorded: Rc<RefCell<Vec<usize>>> = Rc::new(RefCell::new(Vec::new()));
+        let _handles: Vec<SchedulerHandle> = insert_logging_tasks(&scheduler, &recorded);
+        scheduler.start_recording();
+        scheduler.poll();
+        scheduler.poll();
+        let recording: Recording = scheduler.stop_recording().expect("recording should be in progress");
+        assert_eq!(recording.decisions().len(), 200);
+        assert_eq!(
+            recording.decisions().iter().filter(|decision| decision.ready).count(),
+            100
+        );
+
+        // Replay it on a scheduler that polls tasks in bit order.
+        let recording: Recording = Recording::from_bytes(&recording.to_bytes()).expect("malformed recording");
+        let scheduler: Scheduler = Scheduler::default();
+        let replayed: Rc<RefCell<Vec<usize>>> = Rc::new(RefCell::new(Vec::new()));
+        let handles: Vec<SchedulerHandle> = insert_logging_tasks(&scheduler, &replayed);
+        scheduler.replay(recording);
+        assert!(scheduler.is_replaying());
+        scheduler.poll();
+        scheduler.poll();
+        assert!(!scheduler.is_replaying());
+        assert_eq!(*replayed.borrow(), *recorded.borrow());
+        assert!(handles.iter().all(|handle| handle.has_completed()));
+    }
+
+    #[test]
+    fn scheduler_replay_keeps_wakeups() {
+        // Record a task that yields once.
+        let scheduler: Scheduler = Scheduler::default();
+        let _handle: SchedulerHandle = scheduler
+            .insert(TestTask::new(async {
+                yield_now().await;
+            }))
+            .expect("insert() failed");
+        scheduler.start_recording();
+        scheduler.poll();
+        scheduler.poll();
+        let recording: Recording = scheduler.stop_recording().expect("recording should be in progress");
+
+        // Replay it alongside a task that is woken up during the replay, but that the recording does not poll.
+        let scheduler: Scheduler = Scheduler::default();
+        let _handle: SchedulerHandle = scheduler
+            .insert(TestTask::new(async {
+                yield_now().await;
+            }))
+            .expect("insert() failed");
+        let polls: Rc<Cell<usize>> = Rc::new(Cell::new(0));
+        let polls_: Rc<Cell<usize>> = polls.clone();
+        let woken: SchedulerHandle = scheduler
+            .insert_parked(TestTask::new(async move {
+                polls_.set(polls_.get() + 1);
+            }))
+            .expect("insert_parked() failed");
+        scheduler.replay(recording);
+        scheduler.poll();
+        scheduler.wake(&woken);
+        scheduler.poll();
+        assert!(!scheduler.is_replaying());
+        assert_eq!(polls.get(), 0);
+
+        scheduler.poll();
+        assert_eq!(polls.get(), 1);
+        assert!(woken.has_completed());
+    }
+
+    #[test]
+    #[should_panic(expected = "replay diverged")]
+    fn scheduler_replay_diverged() {
+        let scheduler: Scheduler = Scheduler::default();
+        let _handle: SchedulerHandle = match scheduler.insert(DummyFuture::new(1)) {
+            Some(handle) => handle,
+            None => panic!("insert() failed"),
+        };
+        scheduler.start_recording();
+        scheduler.poll();
+        let recording: Recording = scheduler.stop_recording().expect("recording should be in progress");
+
+        // This task completes right away, whereas the recorded one did not.
+        let scheduler: Scheduler = Scheduler::default();
+        let _handle: SchedulerHandle = match scheduler.insert(DummyFuture::new(0)) {
+            Some(handle) => handle,
+            None => panic!("insert() failed"),
+        };
+        scheduler.replay(recording);
+        scheduler.poll();
+    }
+
+    /// Future that hands its waker over to another thread, which wakes it up after a while.
+    #[cfg(target_os = "linux")]
+    struct RemoteFuture {
+        thread: Option<thread::JoinHandle<()>>,
+    }
+
+    #[cfg(target_os = "linux")]
+    impl Future for RemoteFuture {
+        type Output = ();
+
+        fn poll(self: Pin<&mut Self>, ctx: &mut Context) -> Poll<Self::Output> {
+            let self_: &mut RemoteFuture = self.get_mut();
+            match self_.thread.take() {
+                Some(thread) => {
+                    thread.join().expect("thread panicked");
+                    Poll::Ready(())
+                },
+                None => {
+                    let waker: Waker = ctx.waker().clone();
+                    self_.thread = Some(thread::spawn(move || {
+                        thread::sleep(Duration::from_millis(10));
+                        waker.wake();
+                    }));
+                    Poll::Pending
+                },
+            }
+        }
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn scheduler_remote_wakeup() {
+        let scheduler: Scheduler = Scheduler::default();
+        scheduler
+            .enable_remote_wakeups()
+            .expect("enable_remote_wakeups() failed");
+        let handle: SchedulerHandle = scheduler
+            .insert(TestTask::new(async {
+                RemoteFuture { thread: None }.await;
+                // Local wakeups go through the remote queue too.
+                yield_now().await;
+            }))
+            .expect("insert() failed");
+
+        scheduler.poll();
+        assert!(!scheduler.has_notified());
+        // Blocks until the other thread wakes up the task.
+        scheduler.park(None).expect("park() failed");
+        assert!(scheduler.has_notified());
+        scheduler.poll();
+        scheduler.park(None).expect("park() failed");
+        scheduler.poll();
+        assert!(handle.has_completed());
+    }
+
+    #[test]
+    fn scheduler_completion_queue() {
+        let scheduler: Scheduler = Scheduler::default();
+        scheduler.enable_completion_queue();
+        let mut handles: Vec<SchedulerHandle> = Vec::new();
+        for n in (0..3).rev() {
+            let handle: SchedulerHandle = scheduler
+                .insert(TestTask::new(async move {
+                    for _ in 0..n {
+                        yield_now().await;
+                    }
+                }))
+                .expect("insert() failed");
+            handles.push(handle);
+        }
+
+        scheduler.poll();
+        assert_eq!(scheduler.drain_completed(), [handles[2].key()]);
+        assert!(scheduler.drain_completed().is_empty());
+        scheduler.poll();
+        scheduler.poll();
+        assert_eq!(scheduler.drain_completed(), [handles[1].key(), handles[0].key()]);
+    }
+
+    /// Future that never completes, and counts how many times it is polled.
+    struct CountPolls(Rc<Cell<usize>>);
+
+    impl Future for CountPolls {
+        type Output = ();
+
+        fn poll(self: Pin<&mut Self>, _ctx: &mut Context) -> Poll<()> {
+            self.0.set(self.0.get() + 1);
+            Poll::Pending
+        }
+    }
+
+    #[test]
+    fn scheduler_wake() {
+        let scheduler: Scheduler = Scheduler::default();
+        let polls: Rc<Cell<usize>> = Rc::new(Cell::new(0));
+        let mut handles: Vec<SchedulerHandle> = Vec::new();
+        // Spans two pages.
+        for _ in 0..100 {
+            let handle: SchedulerHandle = scheduler
+                .insert(TestTask::new(CountPolls(polls.clone())))
+                .expect("insert() failed");
+            handles.push(handle);
+        }
+        scheduler.poll();
+        assert_eq!(polls.get(), 100);
+        assert!(!scheduler.has_notified());
+
+        scheduler.wake(&handles[3]);
+        assert!(scheduler.wake_raw(handles[70].key()));
+        assert!(!scheduler.wake_raw(1000));
+        scheduler.poll();
+        assert_eq!(polls.get(), 102);
+
+        let woken: usize = scheduler.wake_many(handles.iter().step_by(10).map(|handle| handle.key()).chain([1000]));
+        assert_eq!(woken, 10);
+        scheduler.poll();
+        assert_eq!(polls.get(), 112);
+    }
+
+    #[test]
+    fn scheduler_insert_parked() {
+        let scheduler: Scheduler = Scheduler::default();
+        let polls: Rc<Cell<usize>> = Rc::new(Cell::new(0));
+        let handles: Vec<SchedulerHandle> = (0..1000)
+            .map(|_| {
+                scheduler
+                    .insert_parked(TestTask::new(CountPolls(polls.clone())))
+                    .expect("insert_parked() failed")
+            })
+            .collect();
+        assert!(!scheduler.has_notified());
+        scheduler.poll();
+        assert_eq!(polls.get(), 0);
+
+        // Parked tasks run once woken up.
+        scheduler.wake(&handles[500]);
+        scheduler.poll();
+        assert_eq!(polls.get(), 1);
+    }
+
+    #[test]
+    fn scheduler_suspend_resume() {
+        let scheduler: Scheduler = Scheduler::default();
+        let polls: Rc<Cell<usize>> = Rc::new(Cell::new(0));
+        let handle: SchedulerHandle = scheduler
+            .insert(TestTask::new(CountPolls(polls.clone())))
+            .expect("insert() failed");
+
+        scheduler.suspend(&handle);
+        assert!(scheduler.is_suspended(&handle));
+        scheduler.poll();
+        scheduler.wake(&handle);
+        scheduler.poll();
+        assert_eq!(polls.get(), 0);
+        assert!(!scheduler.has_notified());
+
+        // Wakeups received while suspended are delivered on resume, once.
+        scheduler.resume(&handle);
+        assert!(!scheduler.is_suspended(&handle));
+        scheduler.poll();
+        scheduler.poll();
+        assert_eq!(polls.get(), 1);
+    }
+
+    #[test]
+    fn scheduler_poll_until_quiescent() {
+        let scheduler: Scheduler = Scheduler::default();
+        let polls: Rc<Cell<usize>> = Rc::new(Cell::new(0));
+        let consumer: SchedulerHandle = scheduler
+            .insert_parked(TestTask::new(CountPolls(polls.clone())))
+            .expect("insert_parked() failed");
+        let scheduler_: Scheduler = scheduler.clone();
+        let key: u64 = consumer.key();
+        let producer: SchedulerHandle = scheduler
+            .insert(TestTask::new(async move {
+                yield_now().await;
+                scheduler_.wake_raw(key);
+            }))
+            .expect("insert() failed");
+
+        // The consumer is polled as soon as the producer wakes it up.
+        assert_eq!(scheduler.poll_until_quiescent(10), 3);
+        assert!(producer.has_completed());
+        assert_eq!(polls.get(), 1);
+
+        // Tasks that keep waking themselves up exhaust the budget.
+        let _spinner: SchedulerHandle = scheduler
+            .insert(TestTask::new(async {
+                loop {
+                    yield_now().await;
+                }
+            }))
+            .expect("insert() failed");
+        assert_eq!(scheduler.poll_until_quiescent(10), 10);
+    }
+
+    #[test]
+    fn scheduler_task_stats() {
+        let clock: Rc<ManualClock> = Rc::new(ManualClock::new());
+        let scheduler: Scheduler = Scheduler::new(clock.clone());
+        scheduler.enable_task_stats();
+        let mut handles: Vec<SchedulerHandle> = Vec::new();
+        for n in 0..3 {
+            let clock_: Rc<ManualClock> = clock.clone();
+            let handle: SchedulerHandle = scheduler
+                .insert(TestTask::new(async move {
+                    for _ in 0..n {
+                        clock_.advance(Duration::from_millis(1));
+                        yield_now().await;
+                    }
+                }))
+                .expect("insert() failed");
+            scheduler.set_tag(&handle, if n == 0 { "control" } else { "tenant" });
+            handles.push(handle);
+        }
+        for _ in 0..3 {
+            scheduler.poll();
+        }
+
+        let polls: Vec<u64> = handles.iter().map(|handle| handle.stats().polls).collect();
+        assert_eq!(polls, [1, 2, 3]);
+        assert_eq!(handles[2].stats().busy, Duration::from_millis(2));
+        let stats: Vec<(u64, TaskStats)> = scheduler.task_stats();
+        assert_eq!(stats.len(), 3);
+        assert_eq!(stats[2], (handles[2].key(), handles[2].stats()));
+
+        // Statistics by tag outlive tasks.
+        handles.truncate(1);
+        scheduler.poll();
+        assert_eq!(scheduler.task_stats().len(), 1);
+        let by_tag: HashMap<Rc<str>, TaskStats> = scheduler.stats_by_tag();
+        assert_eq!(by_tag["tenant"].polls, 5);
+        assert_eq!(by_tag["tenant"].busy, Duration::from_millis(3));
+        assert_eq!(by_tag["control"].polls, 1);
+    }
+
+    #[test]
+    fn scheduler_task_stats_late() {
+        let scheduler: Scheduler = Scheduler::default();
+        let early: SchedulerHandle = scheduler.insert(TestTask::new(yield_now())).expect("insert() failed");
+        assert!(scheduler.inner.borrow().tasks[early.key() as usize].stats.is_none());
+
+        // Only tasks inserted once accounting is enabled are accounted.
+        scheduler.enable_task_stats();
+        let late: SchedulerHandle = scheduler.insert(TestTask::new(yield_now())).expect("insert() failed");
+        scheduler.poll();
+        assert_eq!(early.stats(), TaskStats::default());
+        assert_eq!(late.stats().polls, 1);
+    }
+
+    #[test]
+    fn scheduler_validate() {
+        let scheduler: Scheduler = Scheduler::default();
+        let mut handles: Vec<SchedulerHandle> = Vec::new();
+        for n in 0..70 {
+            let handle: SchedulerHandle = scheduler
+                .insert(TestTask::new(async move {
+                    for _ in 0..(n % 3) {
+                        yield_now().await;
+                    }
+                }))
+                .expect("insert() failed");
+            handles.push(handle);
+            assert_eq!(scheduler.validate(), []);
+        }
+        scheduler.suspend(&handles[65]);
+        scheduler.poll();
+        assert_eq!(scheduler.validate(), []);
+        scheduler.cancel(&handles[1]);
+        drop(scheduler.take(handles.pop().unwrap()));
+        assert_eq!(scheduler.validate(), []);
+
+        // Dropped tasks are freed, including those at the first index of a page.
+        let first: SchedulerHandle = handles.remove(64);
+        drop(handles);
+        drop(first);
+        scheduler.poll();
+        assert_eq!(scheduler.validate(), []);
+        assert!(scheduler.from_raw_handle(0).is_none());
+        assert!(scheduler.from_raw_handle(64).is_none());
+        assert!(scheduler.tasks().is_empty());
+    }
+
+    #[test]
+    fn scheduler_validate_violations() {
+        let scheduler: Scheduler = Scheduler::default();
+        let handle: SchedulerHandle = scheduler.insert(TestTask::new(async {})).expect("insert() failed");
+        scheduler.poll();
+        // Stale notifications of completed tasks are ignored.
+        scheduler.inner.borrow().pages[0].notify(handle.key() as usize);
+        assert_eq!(scheduler.validate(), []);
+
+        scheduler.inner.borrow().pages[0].mark_dropped(5);
+        scheduler.inner.borrow().pages[0].mark_dropped(handle.key() as usize);
+        let forged: SchedulerHandle = scheduler
+            .from_raw_handle(handle.key())
+            .expect("from_raw_handle() failed");
+        scheduler.inner.borrow().pages[0].refcount_dec();
+
+        assert_eq!(
+            scheduler.validate(),
+            [
+                Violation::VacantFlag {
+                    key: 5,
+                    flag: TaskFlag::Dropped,
+                },
+                Violation::DroppedWithHandle {
+                    key: handle.key(),
+                    handles: 2,
+                },
+                Violation::RefcountBelowHandles {
+                    page_ix: 0,
+                    refcount: 2,
+                    handles: 2,
+                },
+            ]
+        );
+        scheduler.inner.borrow().pages[0].refcount_inc();
+        scheduler.inner.borrow().pages[0].clear(5);
+        forged.into_raw();
+    }
+
+    /// Inserts a task that spawns a child, which stays pending and holds `alive`. The child handle is stored in
+    /// `child`.
+    fn insert_parent(
+        scheduler: &Scheduler,
+        alive: Rc<()>,
+        child: Rc<RefCell<Option<SchedulerHandle>>>,
+    ) -> SchedulerHandle {
+        let scheduler_: Scheduler = scheduler.clone();
+        scheduler
+            .insert(TestTask::new(async move {
+                let handle: SchedulerHandle = scheduler_
+                    .insert(TestTask::new(async move {
+                        let _alive: Rc<()> = alive;
+                        future::pending::<()>().await;
+                    }))
+                    .expect("insert() failed");
+                *child.borrow_mut() = Some(handle);
+                future::pending::<()>().await;
+            }))
+            .expect("insert() failed")
+    }
+
+    #[test]
+    fn scheduler_task_tree() {
+        let scheduler: Scheduler = Scheduler::default();
+        let alive: Rc<()> = Rc::new(());
+        let child: Rc<RefCell<Option<SchedulerHandle>>> = Rc::new(RefCell::new(None));
+        let parent: SchedulerHandle = insert_parent(&scheduler, alive.clone(), child.clone());
+
+        scheduler.poll();
+        let child_key: u64 = child.borrow().as_ref().unwrap().key();
+        assert_eq!(scheduler.parent(child_key), Some(parent.key()));
+        let tree: Vec<TaskNode> = scheduler.task_tree();
+        assert_eq!(
+            tree,
+            [TaskNode {
+                key: parent.key(),
+                name: None,
+                completed: false,
+                children: vec![TaskNode {
+                    key: child_key,
+                    name: None,
+                    completed: false,
+                    children: Vec::new(),
+                }],
+            }]
+        );
+        assert_eq!(
+            tree[0].to_string(),
+            format!("task {}\n  task {}\n", parent.key(), child_key)
+        );
+
+        // Cancelling the parent cancels the child too.
+        scheduler.cancel(&parent);
+        assert!(parent.has_completed());
+        assert!(child.borrow().as_ref().unwrap().has_completed());
+        assert_eq!(Rc::strong_count(&alive), 1);
+    }
+
+    #[test]
+    fn scheduler_task_tree_drop() {
+        let scheduler: Scheduler = Scheduler::default();
+        let alive: Rc<()> = Rc::new(());
+        let child: Rc<RefCell<Option<SchedulerHandle>>> = Rc::new(RefCell::new(None));
+        let unrelated: SchedulerHandle = scheduler
+            .insert(TestTask::new(future::pending::<()>()))
+            .expect("insert() failed");
+
+        // The parent detaches the handle of its child.
+        let scheduler_: Scheduler = scheduler.clone();
+        let (alive_, child_): (Rc<()>, Rc<RefCell<Option<SchedulerHandle>>>) = (alive.clone(), child.clone());
+        let parent: SchedulerHandle = scheduler
+            .insert(TestTask::new(async move {
+                let handle: SchedulerHandle = insert_parent(&scheduler_, alive_, child_);
+                handle.into_raw();
+                future::pending::<()>().await;
+            }))
+            .expect("insert() failed");
+
+        scheduler.poll();
+        scheduler.poll();
+        assert_eq!(Rc::strong_count(&alive), 2);
+        assert_eq!(scheduler.task_tree().len(), 2);
+
+        // Dropping the handle of the parent cancels all of its descendants.
+        drop(parent);
+        scheduler.poll();
+        assert_eq!(Rc::strong_count(&alive), 1);
+        assert!(child.borrow().as_ref().unwrap().has_completed());
+        assert!(!unrelated.has_completed());
+    }
+
+    #[test]
+    fn scheduler_task_tree_cancel_from_child() {
+        let scheduler: Scheduler = Scheduler::default();
+        let alive: Rc<()> = Rc::new(());
+        let parent: Rc<RefCell<Option<SchedulerHandle>>> = Rc::new(RefCell::new(None));
+        let detached: Rc<RefCell<Option<SchedulerHandle>>> = Rc::new(RefCell::new(None));
+
+        // The child cancels its parent, and thus itself, while it is polled.
+        let (scheduler_, alive_): (Scheduler, Rc<()>) = (scheduler.clone(), alive.clone());
+        let parent_: Rc<RefCell<Option<SchedulerHandle>>> = parent.clone();
+        let detached_: Rc<RefCell<Option<SchedulerHandle>>> = detached.clone();
+        let handle: SchedulerHandle = scheduler
+            .insert(TestTask::new(async move {
+                let scheduler__: Scheduler = scheduler_.clone();
+                let _child: SchedulerHandle = scheduler_
+                    .insert(TestTask::new(async move {
+                        let _alive: Rc<()> = alive_;
+                        let handle: SchedulerHandle = scheduler__
+                            .insert_with(
+                                TestTask::new(future::pending::<()>()),
+                                TaskAttributes::default().detached(),
+                            )
+                            .expect("insert_with() failed");
+                        *detached_.borrow_mut() = Some(handle);
+                        scheduler__.cancel(parent_.borrow().as_ref().unwrap());
+                        future::pending::<()>().await;
+                    }))
+                    .expect("insert() failed");
+                future::pending::<()>().await;
+            }))
+            .expect("insert() failed");
+        *parent.borrow_mut() = Some(handle);
+
+        scheduler.poll();
+        scheduler.poll();
+        assert!(parent.borrow().as_ref().unwrap().has_completed());
+        assert_eq!(Rc::strong_count(&alive), 1);
+
+        // Tasks inserted as detached are not cancelled along with the task that inserted them.
+        let detached: SchedulerHandle = detached.borrow_mut().take().unwrap();
+        assert_eq!(scheduler.parent(detached.key()), None);
+        assert!(!detached.has_completed());
     }
 
     #[bench]
@@ -0,0 +1,102 @@
+// Copyright (c) Microsoft Corporation.
+// Licensed under the MIT license.
+
+//! Deterministic simulation mode for the [crate::Scheduler].
+//!
+//! In simulation mode the order in which notified tasks are polled is permuted
+//! by a seeded pseudo-random number generator, rather than being fixed by bit
+//! position. Running the same workload with the same seed always produces the
+//! same interleaving, thus a failing interleaving may be reproduced from its
+//! seed.
+
+//==============================================================================
+// Imports
+//==============================================================================
+
+use crate::rng::Rng;
+use ::std::{
+    cell::Cell,
+    rc::Rc,
+    thread,
+};
+
+//==============================================================================
+// Structures
+//==============================================================================
+
+/// Simulation State
+pub struct Simulation {
+    /// Permutes the poll order.
+    rng: Rng,
+    /// Reports the seed if the simulation fails.
+    reporter: SeedReporter,
+}
+
+/// Seed Reporter
+///
+/// Reports the seed of a pseudo-random run of the scheduler if it is dropped
+/// while the current thread is panicking. The seed is reported at most once.
+#[derive(Clone)]
+pub struct SeedReporter {
+    /// What the seed drives.
+    label: &'static str,
+    /// Seed of the run.
+    seed: u64,
+    /// Flags whether or not the seed was already reported.
+    reported: Rc<Cell<bool>>,
+}
+
+//==============================================================================
+// Associate Functions
+//==============================================================================
+
+/// Associate Functions for Simulations
+impl Simulation {
+    /// Creates a simulation whose poll order is determined by `seed`.
+    pub fn new(seed: u64) -> Self {
+        Self {
+            rng: Rng::new(seed),
+            reporter: SeedReporter::new("simulation", seed),
+        }
+    }
+
+    /// Returns the seed of the target [Simulation].
+    pub fn seed(&self) -> u64 {
+        self.reporter.seed
+    }
+
+    /// Permutes the order in which the tasks identified by `keys` are polled.
+    pub fn shuffle(&mut self, keys: &mut [u64]) {
+        self.rng.shuffle(keys)
+    }
+
+    /// Returns a guard that reports the seed of the target [Simulation] if a panic unwinds through it.
+    pub fn reporter(&self) -> SeedReporter {
+        self.reporter.clone()
+    }
+}
+
+/// Associate Functions for Seed Reporters
+impl SeedReporter {
+    /// Creates a reporter for a run of `label` that is initialized with `seed`.
+    pub fn new(label: &'static str, seed: u64) -> Self {
+        Self {
+            label,
+            seed,
+            reported: Rc::new(Cell::new(false)),
+        }
+    }
+}
+
+//==============================================================================
+// Trait Implementations
+//==============================================================================
+
+/// Drop Trait Implementation for Seed Reporters
+impl Drop for SeedReporter {
+    fn drop(&mut self) {
+        if thread::panicking() && !self.reported.replace(true) {
+            eprintln!("scheduler: {} failed with seed {}", self.label, self.seed);
+        }
+    }
+}
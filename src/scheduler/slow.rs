@@ -0,0 +1,196 @@
+// Copyright (c) Microsoft Corporation.
+// Licensed under the MIT license.
+
+//! Slow-poll detection for the [crate::Scheduler].
+//!
+//! Tasks are polled on the single scheduler thread, so a task that takes long
+//! to return from a poll delays all other tasks. Once detection is enabled,
+//! every poll is timed: polls that exceed a threshold are reported to a
+//! callback, and the slowest tasks are kept in a bounded list.
+
+//==============================================================================
+// Imports
+//==============================================================================
+
+use ::std::{
+    mem,
+    panic::Location,
+    rc::Rc,
+    time::Duration,
+};
+
+//==============================================================================
+// Structures
+//==============================================================================
+
+/// Slow Poll
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SlowPoll {
+    /// Key of the task.
+    pub key: u64,
+    /// Name of the task, if any.
+    pub name: Option<Rc<str>>,
+    /// Type name of the future of the task.
+    pub type_name: &'static str,
+    /// Location from which the task was inserted, if known.
+    pub location: Option<&'static Location<'static>>,
+    /// Time that the poll took.
+    pub duration: Duration,
+}
+
+/// Slow Poll Callback
+pub type SlowPollFn = Box<dyn FnMut(&SlowPoll)>;
+
+/// Slow-Poll Detector
+pub struct SlowPolls {
+    /// Polls that take at least this long are reported.
+    threshold: Duration,
+    /// Maximum number of tasks in the list of slowest tasks.
+    capacity: usize,
+    /// Slowest poll of the slowest tasks, slowest first.
+    slowest: Vec<SlowPoll>,
+    /// Callback to which slow polls are reported. It is taken out while it runs.
+    callback: Option<SlowPollFn>,
+    /// Slow polls that are yet to be reported.
+    pending: Vec<SlowPoll>,
+}
+
+//==============================================================================
+// Associate Functions
+//==============================================================================
+
+/// Associate Functions for Slow-Poll Detectors
+impl SlowPolls {
+    /// Creates a detector that reports polls that take at least `threshold` to `callback`, and keeps the `capacity`
+    /// slowest tasks.
+    pub fn new(threshold: Duration, capacity: usize, callback: SlowPollFn) -> Self {
+        Self {
+            threshold,
+            capacity,
+            slowest: Vec::with_capacity(capacity + 1),
+            callback: Some(callback),
+            pending: Vec::new(),
+        }
+    }
+
+    /// Accounts for `poll`.
+    pub fn record(&mut self, poll: SlowPoll) {
+        let (key, duration): (u64, Duration) = (poll.key, poll.duration);
+        if duration >= self.threshold {
+            self.pending.push(poll.clone());
+        }
+
+        // Keep a single entry per task, for its slowest poll.
+        match self.slowest.iter().position(|slow| slow.key == key) {
+            Some(ix) if self.slowest[ix].duration >= duration => return,
+            Some(ix) => {
+                self.slowest.remove(ix);
+            },
+            None => (),
+        }
+        let ix: usize = self.slowest.partition_point(|slow| slow.duration >= duration);
+        if ix < self.capacity {
+            self.slowest.insert(ix, poll);
+            self.slowest.truncate(self.capacity);
+        }
+    }
+
+    /// Forgets the task identified by `key`, whose key may then be reused.
+    pub fn remove(&mut self, key: u64) {
+        self.slowest.retain(|slow| slow.key != key);
+    }
+
+    /// Returns the slowest poll of the slowest tasks, slowest first.
+    pub fn slowest(&self) -> &[SlowPoll] {
+        &self.slowest
+    }
+
+    /// Takes out the slow polls that are yet to be reported, along with the callback to report them to.
+    pub fn take_pending(&mut self) -> Option<(SlowPollFn, Vec<SlowPoll>)> {
+        if self.pending.is_empty() {
+            return None;
+        }
+        let callback: SlowPollFn = self.callback.take()?;
+        Some((callback, mem::take(&mut self.pending)))
+    }
+
+    /// Puts back the callback that was taken out to report slow polls.
+    pub fn restore(&mut self, callback: SlowPollFn) {
+        self.callback = Some(callback);
+    }
+}
+
+//==============================================================================
+// Unit Tests
+//==============================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::SlowPolls;
+    use crate::{
+        test_utils::{
+            yield_now,
+            TestTask,
+        },
+        ManualClock,
+        Scheduler,
+        SchedulerHandle,
+        SlowPoll,
+    };
+    use ::std::{
+        cell::RefCell,
+        rc::Rc,
+        time::Duration,
+    };
+
+    #[test]
+    fn slowest_tasks() {
+        let mut slow: SlowPolls = SlowPolls::new(Duration::from_millis(10), 2, Box::new(|_: &SlowPoll| ()));
+        for (key, millis) in [(0, 3), (1, 1), (2, 5), (0, 2), (1, 4)] {
+            slow.record(SlowPoll {
+                key,
+                name: None,
+                type_name: "task",
+                location: None,
+                duration: Duration::from_millis(millis),
+            });
+        }
+        let keys: Vec<u64> = slow.slowest().iter().map(|slow| slow.key).collect();
+        assert_eq!(keys, [2, 1]);
+        assert!(slow.take_pending().is_none());
+    }
+
+    #[test]
+    fn slow_poll_callback() {
+        let clock: Rc<ManualClock> = Rc::new(ManualClock::new());
+        let scheduler: Scheduler = Scheduler::new(clock.clone());
+        let reported: Rc<RefCell<Vec<SlowPoll>>> = Rc::new(RefCell::new(Vec::new()));
+        let reported_: Rc<RefCell<Vec<SlowPoll>>> = reported.clone();
+        scheduler.enable_slow_polls(Duration::from_millis(5), 4, move |slow: &SlowPoll| {
+            reported_.borrow_mut().push(slow.clone())
+        });
+
+        let _fast: SchedulerHandle = scheduler
+            .insert(TestTask::new(async {
+                yield_now().await;
+            }))
+            .expect("insert() failed");
+        let blocking: SchedulerHandle = scheduler
+            .insert(TestTask::new(async move {
+                clock.advance(Duration::from_millis(10));
+            }))
+            .expect("insert() failed");
+
+        scheduler.poll();
+        scheduler.poll();
+        let reported: Vec<SlowPoll> = reported.borrow().clone();
+        assert_eq!(reported.len(), 1);
+        assert_eq!(reported[0].key, blocking.key());
+        assert!(reported[0].type_name.ends_with("TestTask"));
+        assert_eq!(reported[0].duration, Duration::from_millis(10));
+
+        let slowest: Vec<SlowPoll> = scheduler.slowest_polls();
+        assert_eq!(slowest.len(), 2);
+        assert_eq!(slowest[0], reported[0]);
+    }
+}
@@ -0,0 +1,41 @@
+// Copyright (c) Microsoft Corporation.
+// Licensed under the MIT license.
+
+//! Per-task CPU time accounting for the [crate::Scheduler].
+//!
+//! Once accounting is enabled, the scheduler accumulates the time that each
+//! task spends being polled, along with the number of polls. Statistics are
+//! available per task, and per tag for tasks that were tagged, in which case
+//! they also account for tasks that are gone.
+
+//==============================================================================
+// Imports
+//==============================================================================
+
+use ::std::time::Duration;
+
+//==============================================================================
+// Structures
+//==============================================================================
+
+/// Statistics of a Task
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct TaskStats {
+    /// Time spent being polled.
+    pub busy: Duration,
+    /// Number of polls.
+    pub polls: u64,
+}
+
+//==============================================================================
+// Associate Functions
+//==============================================================================
+
+/// Associate Functions for Task Statistics
+impl TaskStats {
+    /// Accounts for a poll that took `elapsed`.
+    pub fn record(&mut self, elapsed: Duration) {
+        self.busy += elapsed;
+        self.polls += 1;
+    }
+}
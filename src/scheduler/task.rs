@@ -0,0 +1,138 @@
+// Copyright (c) Microsoft Corporation.
+// Licensed under the MIT license.
+
+//! Attributes of tasks in the [crate::Scheduler].
+//!
+//! Tasks may be inserted with a name and a tag, and the scheduler records the
+//! location in the source code from which each task was inserted. These show
+//! up wherever tasks are reported, so that a misbehaving task can be told
+//! apart from its numeric key.
+
+//==============================================================================
+// Imports
+//==============================================================================
+
+use ::std::{
+    fmt,
+    panic::Location,
+    rc::Rc,
+};
+
+//==============================================================================
+// Structures
+//==============================================================================
+
+/// Attributes of a Task
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct TaskAttributes {
+    /// Name of the task.
+    pub name: Option<String>,
+    /// Tag of the task, under which its statistics are grouped.
+    pub tag: Option<String>,
+    /// Whether or not the task is detached from the task that inserts it, rather than becoming its child.
+    pub detached: bool,
+}
+
+/// Description of a Task
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TaskDescription {
+    /// Key of the task.
+    pub key: u64,
+    /// Name of the task, if any.
+    pub name: Option<Rc<str>>,
+    /// Tag of the task, if any.
+    pub tag: Option<Rc<str>>,
+    /// Type name of the future of the task.
+    pub type_name: &'static str,
+    /// Location from which the task was inserted, if known.
+    pub location: Option<&'static Location<'static>>,
+    /// Whether or not the task has completed.
+    pub completed: bool,
+}
+
+//==============================================================================
+// Associate Functions
+//==============================================================================
+
+/// Associate Functions for Task Attributes
+impl TaskAttributes {
+    /// Creates attributes for a task named `name`.
+    pub fn named(name: &str) -> Self {
+        Self {
+            name: Some(name.to_string()),
+            ..Default::default()
+        }
+    }
+
+    /// Sets the tag of the target [TaskAttributes] to `tag`.
+    pub fn tag(mut self, tag: &str) -> Self {
+        self.tag = Some(tag.to_string());
+        self
+    }
+
+    /// Detaches the task from the task that inserts it, so that it is not cancelled along with the latter.
+    pub fn detached(mut self) -> Self {
+        self.detached = true;
+        self
+    }
+}
+
+//==============================================================================
+// Trait Implementations
+//==============================================================================
+
+/// Display Trait Implementation for Task Descriptions
+impl fmt::Display for TaskDescription {
+    /// Writes the key, name, tag, type and location of the task, on a single line.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "task {}", self.key)?;
+        if let Some(name) = self.name.as_ref() {
+            write!(f, " \"{}\"", name)?;
+        }
+        if let Some(tag) = self.tag.as_ref() {
+            write!(f, " [{}]", tag)?;
+        }
+        write!(f, " ({})", self.type_name)?;
+        if let Some(location) = self.location {
+            write!(f, " inserted at {}", location)?;
+        }
+        if self.completed {
+            write!(f, " (completed)")?;
+        }
+        Ok(())
+    }
+}
+
+//==============================================================================
+// Unit Tests
+//==============================================================================
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        test_utils::TestTask,
+        Scheduler,
+        SchedulerHandle,
+        TaskAttributes,
+        TaskDescription,
+    };
+    use ::std::future;
+
+    #[test]
+    fn task_attributes() {
+        let scheduler: Scheduler = Scheduler::default();
+        let attributes: TaskAttributes = TaskAttributes::named("listener").tag("tenant");
+        let line: u32 = line!() + 2;
+        let handle: SchedulerHandle = scheduler
+            .insert_with(TestTask::new(future::pending::<()>()), attributes)
+            .expect("insert_with() failed");
+
+        let description: TaskDescription = scheduler.describe(handle.key()).expect("describe() failed");
+        assert_eq!(description.name.as_deref(), Some("listener"));
+        assert_eq!(description.tag.as_deref(), Some("tenant"));
+        let location: &str = &format!("{}:{}", file!(), line);
+        assert!(description.to_string().contains(location));
+        assert!(format!("{:?}", scheduler).contains("listener"));
+        assert_eq!(scheduler.tasks(), [description]);
+    }
+}
@@ -0,0 +1,237 @@
+// Copyright (c) Microsoft Corporation.
+// Licensed under the MIT license.
+
+//! Parent/child tree of tasks in the [crate::Scheduler].
+//!
+//! A task that is inserted while another task is being polled becomes a child
+//! of the latter. When a task is cancelled or removed from the scheduler, all
+//! of its descendants are cancelled: their futures are dropped and they are
+//! flagged as completed, so that whoever holds their handles sees them done.
+
+//==============================================================================
+// Imports
+//==============================================================================
+
+use crate::{
+    SchedulerFuture,
+    TaskDescription,
+};
+use ::std::{
+    any::Any,
+    fmt,
+    future::Future,
+    mem,
+    pin::Pin,
+    rc::Rc,
+    task::{
+        Context,
+        Poll,
+    },
+};
+
+//==============================================================================
+// Structures
+//==============================================================================
+
+/// Links of a Task in the Tree
+#[derive(Clone, Debug, Default)]
+struct Links {
+    /// Key of the parent task, if any.
+    parent: Option<u64>,
+    /// Keys of the child tasks.
+    children: Vec<u64>,
+}
+
+/// Task Tree
+#[derive(Default)]
+pub struct TaskTree {
+    /// Links of tasks, by key.
+    links: Vec<Links>,
+}
+
+/// Snapshot of a Task in the Tree
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TaskNode {
+    /// Key of the task.
+    pub key: u64,
+    /// Name of the task, if any.
+    pub name: Option<Rc<str>>,
+    /// Whether or not the task has completed or was cancelled.
+    pub completed: bool,
+    /// Child tasks.
+    pub children: Vec<TaskNode>,
+}
+
+/// Future of a Cancelled Task
+///
+/// This future takes the place of the future of a cancelled task until its handle is dropped.
+pub struct Cancelled;
+
+//==============================================================================
+// Associate Functions
+//==============================================================================
+
+/// Associate Functions for Task Trees
+impl TaskTree {
+    /// Adds the task identified by `key` to the target [TaskTree], as a child of `parent`. Root tasks are not tracked,
+    /// since the links of removed tasks are already reset.
+    pub fn insert(&mut self, key: u64, parent: Option<u64>) {
+        let parent: u64 = match parent {
+            Some(parent) => parent,
+            None => return,
+        };
+        let ix: usize = key as usize;
+        // The parent may be a root task, which has no links yet.
+        let len: usize = ix.max(parent as usize) + 1;
+        if self.links.len() < len {
+            self.links.resize_with(len, Links::default);
+        }
+        self.links[ix] = Links {
+            parent: Some(parent),
+            children: Vec::new(),
+        };
+        self.links[parent as usize].children.push(key);
+    }
+
+    /// Removes the task identified by `key` from the target [TaskTree]. Its children become roots, and are returned.
+    pub fn remove(&mut self, key: u64) -> Vec<u64> {
+        let links: Links = match self.links.get_mut(key as usize) {
+            Some(links) => mem::take(links),
+            None => return Vec::new(),
+        };
+        if let Some(parent) = links.parent {
+            self.links[parent as usize].children.retain(|child| *child != key);
+        }
+        for child in &links.children {
+            self.links[*child as usize].parent = None;
+        }
+        links.children
+    }
+
+    /// Returns the key of the parent of the task identified by `key`, if any.
+    pub fn parent(&self, key: u64) -> Option<u64> {
+        self.links.get(key as usize)?.parent
+    }
+
+    /// Returns the keys of the children of the task identified by `key`.
+    pub fn children(&self, key: u64) -> &[u64] {
+        self.links.get(key as usize).map_or(&[], |links| &links.children)
+    }
+
+    /// Returns the keys of the task identified by `key` and all of its descendants, parents first.
+    pub fn subtree(&self, key: u64) -> Vec<u64> {
+        let mut keys: Vec<u64> = vec![key];
+        let mut ix: usize = 0;
+        while ix < keys.len() {
+            keys.extend_from_slice(self.children(keys[ix]));
+            ix += 1;
+        }
+        keys
+    }
+
+    /// Builds a snapshot of the subtree rooted at the task identified by `key`. The `describe` function describes a
+    /// task.
+    pub fn snapshot(&self, key: u64, describe: &dyn Fn(u64) -> TaskDescription) -> TaskNode {
+        let description: TaskDescription = describe(key);
+        TaskNode {
+            key,
+            name: description.name,
+            completed: description.completed,
+            children: self
+                .children(key)
+                .iter()
+                .map(|child| self.snapshot(*child, describe))
+                .collect(),
+        }
+    }
+}
+
+/// Associate Functions for Task Snapshots
+impl TaskNode {
+    /// Writes the target [TaskNode] and its children to `f`, indented by `depth` levels.
+    fn fmt_indented(&self, f: &mut fmt::Formatter<'_>, depth: usize) -> fmt::Result {
+        write!(f, "{:indent$}task {}", "", self.key, indent = 2 * depth)?;
+        if let Some(name) = self.name.as_ref() {
+            write!(f, " \"{}\"", name)?;
+        }
+        writeln!(f, "{}", if self.completed { " (completed)" } else { "" })?;
+        for child in &self.children {
+            child.fmt_indented(f, depth + 1)?;
+        }
+        Ok(())
+    }
+}
+
+//==============================================================================
+// Trait Implementations
+//==============================================================================
+
+/// Display Trait Implementation for Task Snapshots
+impl fmt::Display for TaskNode {
+    /// Writes one task per line, with children indented under their parent.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.fmt_indented(f, 0)
+    }
+}
+
+/// Future Trait Implementation for Cancelled Tasks
+impl Future for Cancelled {
+    type Output = ();
+
+    fn poll(self: Pin<&mut Self>, _ctx: &mut Context) -> Poll<()> {
+        Poll::Ready(())
+    }
+}
+
+/// Scheduler Future Trait Implementation for Cancelled Tasks
+impl SchedulerFuture for Cancelled {
+    fn as_any(self: Box<Self>) -> Box<dyn Any> {
+        self
+    }
+
+    fn get_future(&self) -> &dyn Future<Output = ()> {
+        self
+    }
+}
+
+//==============================================================================
+// Unit Tests
+//==============================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::TaskTree;
+
+    #[test]
+    fn tree_links() {
+        let mut tree: TaskTree = TaskTree::default();
+        tree.insert(0, None);
+        tree.insert(1, Some(0));
+        tree.insert(2, Some(1));
+        tree.insert(3, Some(0));
+
+        assert_eq!(tree.subtree(0), [0, 1, 3, 2]);
+        assert_eq!(tree.parent(2), Some(1));
+
+        assert_eq!(tree.remove(1), [2]);
+        assert_eq!(tree.parent(2), None);
+        assert_eq!(tree.children(0), [3]);
+    }
+
+    #[test]
+    fn tree_untracked_root() {
+        let mut tree: TaskTree = TaskTree::default();
+        tree.insert(5, None);
+        tree.insert(2, Some(5));
+
+        assert_eq!(tree.subtree(5), [5, 2]);
+        assert_eq!(tree.parent(5), None);
+        assert_eq!(tree.parent(2), Some(5));
+
+        // Once removed, the key may be reused by a root task.
+        assert_eq!(tree.remove(2), []);
+        tree.insert(2, None);
+        assert_eq!(tree.parent(2), None);
+        assert!(tree.children(5).is_empty());
+    }
+}
@@ -0,0 +1,82 @@
+// Copyright (c) Microsoft Corporation.
+// Licensed under the MIT license.
+
+//! Invariants of the internal state of the [crate::Scheduler].
+//!
+//! The state of a task is spread across the pinned slab that stores its
+//! future, the flags of the page that holds its status, and the tree of
+//! tasks. [crate::Scheduler::validate] cross-checks them, and reports each
+//! inconsistency as a [Violation].
+
+//==============================================================================
+// Imports
+//==============================================================================
+
+use ::std::fmt;
+
+//==============================================================================
+// Structures
+//==============================================================================
+
+/// Flag of a Task in a Page
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TaskFlag {
+    /// The task has completed.
+    Completed,
+    /// The handle of the task was dropped.
+    Dropped,
+    /// The task is suspended.
+    Suspended,
+}
+
+/// Violation of an Invariant
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Violation {
+    /// A task is stored in the slab, but there is no page to hold its status.
+    MissingPage { key: u64 },
+    /// A flag is set for a key that holds no task.
+    VacantFlag { key: u64, flag: TaskFlag },
+    /// The dropped flag of a task is set, but its page counts more handles than its tasks that were not dropped, by
+    /// `handles`.
+    DroppedWithHandle { key: u64, handles: u64 },
+    /// A page has a lower reference count than the handles to its tasks. Wakers are not counted, so this is a lower
+    /// bound.
+    RefcountBelowHandles {
+        page_ix: usize,
+        refcount: u64,
+        handles: u64,
+    },
+    /// A task is linked to a parent that does not exist, or that does not list it as a child.
+    BrokenLink { key: u64, parent: u64 },
+}
+
+//==============================================================================
+// Trait Implementations
+//==============================================================================
+
+/// Display Trait Implementation for Violations
+impl fmt::Display for Violation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Violation::MissingPage { key } => write!(f, "task {} has no page", key),
+            Violation::VacantFlag { key, flag } => write!(f, "{:?} flag is set for vacant key {}", flag, key),
+            Violation::DroppedWithHandle { key, handles } => {
+                write!(
+                    f,
+                    "task {} is flagged as dropped but its page has {} extra handles",
+                    key, handles
+                )
+            },
+            Violation::RefcountBelowHandles {
+                page_ix,
+                refcount,
+                handles,
+            } => write!(
+                f,
+                "page {} has a reference count of {} but {} scheduler handles",
+                page_ix, refcount, handles
+            ),
+            Violation::BrokenLink { key, parent } => write!(f, "task {} has a broken link to parent {}", key, parent),
+        }
+    }
+}
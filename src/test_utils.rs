@@ -0,0 +1,93 @@
+// Copyright (c) Microsoft Corporation.
+// Licensed under the MIT license.
+
+//! Helpers that are shared by unit tests.
+
+//==============================================================================
+// Imports
+//==============================================================================
+
+use crate::SchedulerFuture;
+use ::std::{
+    any::Any,
+    future::Future,
+    pin::Pin,
+    task::{
+        Context,
+        Poll,
+    },
+};
+
+//==============================================================================
+// Structures
+//==============================================================================
+
+/// Test Task
+///
+/// Wraps an arbitrary future, so that it may be inserted in a [crate::Scheduler].
+pub struct TestTask(Pin<Box<dyn Future<Output = ()>>>);
+
+/// Yield Future
+///
+/// Returns pending once, waking up the running task, and then completes.
+pub struct YieldNow(bool);
+
+//==============================================================================
+// Associate Functions
+//==============================================================================
+
+/// Associate Functions for Test Tasks
+impl TestTask {
+    /// Creates a test task that runs `future`.
+    pub fn new<F: Future<Output = ()> + 'static>(future: F) -> Self {
+        Self(Box::pin(future))
+    }
+}
+
+//==============================================================================
+// Standalone Functions
+//==============================================================================
+
+/// Yields control back to the scheduler once.
+pub fn yield_now() -> YieldNow {
+    YieldNow(false)
+}
+
+//==============================================================================
+// Trait Implementations
+//==============================================================================
+
+/// Future Trait Implementation for Test Tasks
+impl Future for TestTask {
+    type Output = ();
+
+    fn poll(self: Pin<&mut Self>, ctx: &mut Context) -> Poll<()> {
+        self.get_mut().0.as_mut().poll(ctx)
+    }
+}
+
+/// Future Trait Implementation for Yield Futures
+impl Future for YieldNow {
+    type Output = ();
+
+    fn poll(self: Pin<&mut Self>, ctx: &mut Context) -> Poll<()> {
+        let self_: &mut YieldNow = self.get_mut();
+        if self_.0 {
+            return Poll::Ready(());
+        }
+        self_.0 = true;
+        ctx.waker().wake_by_ref();
+        Poll::Pending
+    }
+}
+
+/// Scheduler Future Trait Implementation for Test Tasks
+impl SchedulerFuture for TestTask {
+    fn as_any(self: Box<Self>) -> Box<dyn Any> {
+        self
+    }
+
+    fn get_future(&self) -> &dyn Future<Output = ()> {
+        self
+    }
+}
@@ -0,0 +1,153 @@
+// Copyright (c) Microsoft Corporation.
+// Licensed under the MIT license.
+
+//==============================================================================
+// Imports
+//==============================================================================
+
+use ::std::{
+    cell::Cell,
+    time::{
+        Duration,
+        Instant,
+    },
+};
+
+//==============================================================================
+// Traits
+//==============================================================================
+
+/// Clock
+///
+/// This trait describes a source of time for the [crate::Scheduler]. All
+/// timers that are driven by the scheduler read the current time through it.
+pub trait Clock {
+    /// Returns the current time.
+    fn now(&self) -> Instant;
+
+    /// Queries whether or not the target [Clock] is virtual, that is, it does not move along with wall time. Threads
+    /// that wait on a virtual clock do not block, but move the clock ahead instead (see [Clock::skip]).
+    fn is_virtual(&self) -> bool {
+        false
+    }
+
+    /// Moves the target [Clock] `duration` ahead, if it is virtual. Other clocks are left unmodified.
+    fn skip(&self, _duration: Duration) {}
+}
+
+//==============================================================================
+// Structures
+//==============================================================================
+
+/// System Clock
+///
+/// A [Clock] that reads the monotonic clock of the operating system.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct SystemClock;
+
+/// Manual Clock
+///
+/// A [Clock] that only moves forward when explicitly told so. This is intended
+/// for tests that rely on time, so that they run deterministically and do not
+/// have to wait on wall time.
+#[derive(Debug)]
+pub struct ManualClock {
+    /// Current time.
+    now: Cell<Instant>,
+}
+
+//==============================================================================
+// Associate Functions
+//==============================================================================
+
+/// Associate Functions for Manual Clocks
+impl ManualClock {
+    /// Creates a manual clock that is stopped at the current time.
+    pub fn new() -> Self {
+        Self {
+            now: Cell::new(Instant::now()),
+        }
+    }
+
+    /// Moves the target [ManualClock] `duration` ahead.
+    pub fn advance(&self, duration: Duration) {
+        self.now.set(self.now.get() + duration);
+    }
+
+    /// Moves the target [ManualClock] to `instant`, which may not be in the past.
+    pub fn set(&self, instant: Instant) {
+        assert!(instant >= self.now.get(), "clock cannot move backwards");
+        self.now.set(instant);
+    }
+}
+
+//==============================================================================
+// Trait Implementations
+//==============================================================================
+
+/// Clock Trait Implementation for System Clocks
+impl Clock for SystemClock {
+    fn now(&self) -> Instant {
+        Instant::now()
+    }
+}
+
+/// Clock Trait Implementation for Manual Clocks
+impl Clock for ManualClock {
+    fn now(&self) -> Instant {
+        self.now.get()
+    }
+
+    fn is_virtual(&self) -> bool {
+        true
+    }
+
+    fn skip(&self, duration: Duration) {
+        self.advance(duration)
+    }
+}
+
+/// Default Trait Implementation for Manual Clocks
+impl Default for ManualClock {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+//==============================================================================
+// Unit Tests
+//==============================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        Clock,
+        ManualClock,
+    };
+    use ::std::time::{
+        Duration,
+        Instant,
+    };
+
+    #[test]
+    fn manual_clock_advance() {
+        let clock: ManualClock = ManualClock::new();
+        let start: Instant = clock.now();
+        assert_eq!(clock.now(), start);
+
+        clock.advance(Duration::from_secs(30));
+        assert_eq!(clock.now() - start, Duration::from_secs(30));
+
+        clock.set(start + Duration::from_secs(31));
+        assert_eq!(clock.now() - start, Duration::from_secs(31));
+    }
+
+    #[test]
+    #[should_panic]
+    fn manual_clock_backwards() {
+        let clock: ManualClock = ManualClock::new();
+        let start: Instant = clock.now();
+        clock.advance(Duration::from_secs(1));
+        clock.set(start);
+    }
+}
@@ -0,0 +1,203 @@
+// Copyright (c) Microsoft Corporation.
+// Licensed under the MIT license.
+
+//! Implementation of timers that are driven by the [crate::Scheduler].
+//!
+//! Timers are kept in a hierarchical [TimerWheel] that is advanced by the
+//! scheduler at the beginning of every poll. The current time is read from a
+//! pluggable [Clock].
+
+mod clock;
+mod sleep;
+mod wheel;
+
+//==============================================================================
+// Imports
+//==============================================================================
+
+use self::wheel::TimerWheel;
+use ::std::{
+    cell::{
+        Ref,
+        RefCell,
+        RefMut,
+    },
+    future::Future,
+    rc::Rc,
+    task::Waker,
+    time::{
+        Duration,
+        Instant,
+    },
+};
+
+//==============================================================================
+// Exports
+//==============================================================================
+
+pub use self::{
+    clock::{
+        Clock,
+        ManualClock,
+        SystemClock,
+    },
+    sleep::{
+        Elapsed,
+        Interval,
+        Sleep,
+        Tick,
+        Timeout,
+    },
+};
+
+//==============================================================================
+// Constants
+//==============================================================================
+
+/// Granularity of Timers
+const TICK: Duration = Duration::from_millis(1);
+
+//==============================================================================
+// Structures
+//==============================================================================
+
+/// Actual data used by [Timer].
+struct Inner {
+    /// Instant that corresponds to the first tick of the wheel.
+    start: Instant,
+    /// Holds all timers.
+    wheel: TimerWheel,
+}
+
+/// Timer
+///
+/// This structure hands out timer futures and fires them as time advances.
+#[derive(Clone)]
+pub struct Timer {
+    /// Source of time.
+    clock: Rc<dyn Clock>,
+    inner: Rc<RefCell<Inner>>,
+}
+
+//==============================================================================
+// Associate Functions
+//==============================================================================
+
+/// Associate Functions for Timers
+impl Timer {
+    /// Creates a timer that reads time from `clock`.
+    pub fn new(clock: Rc<dyn Clock>) -> Self {
+        let inner: Inner = Inner {
+            start: clock.now(),
+            wheel: TimerWheel::new(),
+        };
+        Self {
+            clock,
+            inner: Rc::new(RefCell::new(inner)),
+        }
+    }
+
+    /// Returns the current time, as seen by the clock of the target [Timer].
+    pub fn now(&self) -> Instant {
+        self.clock.now()
+    }
+
+    /// Returns a future that completes once `duration` has elapsed.
+    pub fn sleep(&self, duration: Duration) -> Sleep {
+        Sleep::new(self.clone(), self.now() + duration)
+    }
+
+    /// Returns a future that completes once `deadline` is reached.
+    pub fn sleep_until(&self, deadline: Instant) -> Sleep {
+        Sleep::new(self.clone(), deadline)
+    }
+
+    /// Runs `future` for at most `duration`.
+    pub fn timeout<F: Future>(&self, future: F, duration: Duration) -> Timeout<F> {
+        Timeout::new(future, self.sleep(duration))
+    }
+
+    /// Returns a stream of ticks that are `period` apart. The first tick happens one period from now.
+    pub fn interval(&self, period: Duration) -> Interval {
+        Interval::new(self.sleep(period), period)
+    }
+
+    /// Returns the instant at which the next timer may fire.
+    pub fn next_deadline(&self) -> Option<Instant> {
+        let inner: Ref<Inner> = self.inner.borrow();
+        let tick: u64 = inner.wheel.next_expiration()?;
+        Some(inner.start + Duration::from_nanos(tick * TICK.as_nanos() as u64))
+    }
+
+    /// Returns for how long a thread that waits on the next timer may block, given that it may not block for longer
+    /// than `timeout`. A virtual clock does not move while the thread blocks, so it is moved to the end of the wait
+    /// instead, and the thread should not block at all.
+    #[cfg(target_os = "linux")]
+    pub(crate) fn park_timeout(&self, timeout: Option<Duration>) -> Option<Duration> {
+        let now: Instant = self.now();
+        let timeout: Option<Duration> = match (timeout, self.next_deadline()) {
+            (Some(timeout), Some(deadline)) => Some(timeout.min(deadline.saturating_duration_since(now))),
+            (None, Some(deadline)) => Some(deadline.saturating_duration_since(now)),
+            (timeout, None) => timeout,
+        };
+        if !self.clock.is_virtual() {
+            return timeout;
+        }
+        if let Some(timeout) = timeout {
+            self.clock.skip(timeout);
+        }
+        Some(Duration::ZERO)
+    }
+
+    /// Fires all timers whose deadlines have been reached, waking up the tasks that wait on them.
+    pub fn advance(&self) {
+        let mut fired: Vec<Waker> = Vec::new();
+        {
+            let mut inner: RefMut<Inner> = self.inner.borrow_mut();
+            // Do not read the clock if there is no timer to fire.
+            if inner.wheel.is_empty() {
+                return;
+            }
+            let now: Instant = self.now();
+            let tick: u64 = ticks(now.saturating_duration_since(inner.start));
+            inner.wheel.advance(tick, &mut fired);
+        }
+        // Wakers are invoked only after releasing the wheel, so that they may register new timers.
+        for waker in fired {
+            waker.wake();
+        }
+    }
+
+    /// Registers a timer that expires at `deadline`, returning its key.
+    fn register(&self, deadline: Instant, waker: &Waker) -> usize {
+        let mut inner: RefMut<Inner> = self.inner.borrow_mut();
+        let elapsed: Duration = deadline.saturating_duration_since(inner.start);
+        // Round up, so that the timer never fires before its deadline.
+        let tick: u64 = ticks(elapsed + TICK - Duration::from_nanos(1));
+        inner.wheel.insert(tick, waker.clone())
+    }
+
+    /// Replaces the waker that is associated with the timer `key`.
+    fn update(&self, key: usize, waker: &Waker) {
+        self.inner.borrow_mut().wheel.update(key, waker)
+    }
+
+    /// Queries whether or not the timer `key` has fired.
+    fn has_fired(&self, key: usize) -> bool {
+        self.inner.borrow().wheel.has_fired(key)
+    }
+
+    /// Removes the timer `key`.
+    fn remove(&self, key: usize) {
+        self.inner.borrow_mut().wheel.remove(key)
+    }
+}
+
+//==============================================================================
+// Standalone Functions
+//==============================================================================
+
+/// Converts `duration` into a number of ticks, rounding down.
+fn ticks(duration: Duration) -> u64 {
+    (duration.as_nanos() / TICK.as_nanos()).min(u64::MAX as u128) as u64
+}
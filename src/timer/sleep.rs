@@ -0,0 +1,417 @@
+// Copyright (c) Microsoft Corporation.
+// Licensed under the MIT license.
+
+//==============================================================================
+// Imports
+//==============================================================================
+
+use super::Timer;
+use crate::consume_budget;
+use ::std::{
+    error::Error,
+    fmt,
+    future::Future,
+    pin::Pin,
+    task::{
+        Context,
+        Poll,
+    },
+    time::{
+        Duration,
+        Instant,
+    },
+};
+
+//==============================================================================
+// Structures
+//==============================================================================
+
+/// Sleep Future
+///
+/// This future completes once its deadline is reached.
+pub struct Sleep {
+    /// Timer that drives this future.
+    timer: Timer,
+    /// Instant at which this future completes.
+    deadline: Instant,
+    /// Key of the corresponding entry in the timer wheel, if registered.
+    key: Option<usize>,
+}
+
+/// Timeout Future
+///
+/// This future runs an underlying future until it completes or a deadline is reached, whatever happens first.
+pub struct Timeout<F: Future> {
+    /// Underlying future, which is pinned along with the timeout.
+    future: F,
+    /// Deadline for the underlying future.
+    sleep: Sleep,
+}
+
+/// Interval
+///
+/// This structure yields ticks that are evenly spaced in time. If a tick is missed, the next one happens one full
+/// period after it is observed.
+pub struct Interval {
+    /// Deadline of the next tick.
+    sleep: Sleep,
+    /// Time between two ticks.
+    period: Duration,
+}
+
+/// Tick Future
+///
+/// This future completes at the next tick of an [Interval].
+pub struct Tick<'a> {
+    interval: &'a mut Interval,
+}
+
+/// Error returned by [Timeout] when its deadline is reached.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Elapsed;
+
+//==============================================================================
+// Associate Functions
+//==============================================================================
+
+/// Associate Functions for Sleep Futures
+impl Sleep {
+    /// Creates a sleep future that completes at `deadline`.
+    pub(super) fn new(timer: Timer, deadline: Instant) -> Self {
+        Self {
+            timer,
+            deadline,
+            key: None,
+        }
+    }
+
+    /// Returns the instant at which the target [Sleep] completes.
+    pub fn deadline(&self) -> Instant {
+        self.deadline
+    }
+
+    /// Moves the deadline of the target [Sleep] to `deadline`.
+    pub fn reset(&mut self, deadline: Instant) {
+        self.cancel();
+        self.deadline = deadline;
+    }
+
+    /// Queries whether or not the deadline of the target [Sleep] was reached.
+    pub fn is_elapsed(&self) -> bool {
+        self.timer.now() >= self.deadline
+    }
+
+    /// Removes the target [Sleep] from the timer wheel.
+    fn cancel(&mut self) {
+        if let Some(key) = self.key.take() {
+            self.timer.remove(key);
+        }
+    }
+}
+
+/// Associate Functions for Timeout Futures
+impl<F: Future> Timeout<F> {
+    /// Creates a timeout future for `future` that expires when `sleep` completes.
+    pub(super) fn new(future: F, sleep: Sleep) -> Self {
+        Self { future, sleep }
+    }
+
+    /// Returns a reference to the underlying future.
+    pub fn get_ref(&self) -> &F {
+        &self.future
+    }
+
+    /// Consumes the target [Timeout] returning the underlying future.
+    pub fn into_inner(self) -> F {
+        self.future
+    }
+}
+
+/// Associate Functions for Intervals
+impl Interval {
+    /// Creates an interval whose first tick happens when `sleep` completes.
+    pub(super) fn new(sleep: Sleep, period: Duration) -> Self {
+        Self { sleep, period }
+    }
+
+    /// Returns the period of the target [Interval].
+    pub fn period(&self) -> Duration {
+        self.period
+    }
+
+    /// Returns a future that completes at the next tick of the target [Interval].
+    pub fn tick(&mut self) -> Tick<'_> {
+        Tick { interval: self }
+    }
+
+    /// Polls for the next tick of the target [Interval], returning the instant at which it was scheduled.
+    pub fn poll_tick(&mut self, ctx: &mut Context) -> Poll<Instant> {
+        match Future::poll(Pin::new(&mut self.sleep), ctx) {
+            Poll::Pending => Poll::Pending,
+            Poll::Ready(()) => {
+                let deadline: Instant = self.sleep.deadline();
+                let now: Instant = self.sleep.timer.now();
+                let mut next: Instant = deadline + self.period;
+                // Skip missed ticks.
+                if next <= now {
+                    next = now + self.period;
+                }
+                self.sleep.reset(next);
+                Poll::Ready(deadline)
+            },
+        }
+    }
+}
+
+//==============================================================================
+// Trait Implementations
+//==============================================================================
+
+/// Future Trait Implementation for Sleep Futures
+impl Future for Sleep {
+    type Output = ();
+
+    /// Polls the target [Sleep].
+    fn poll(self: Pin<&mut Self>, ctx: &mut Context) -> Poll<()> {
+        // A loop over elapsed sleeps should not monopolize the poll pass.
+        if consume_budget(ctx).is_pending() {
+            return Poll::Pending;
+        }
+        let self_: &mut Sleep = self.get_mut();
+        if self_.is_elapsed() {
+            self_.cancel();
+            return Poll::Ready(());
+        }
+        match self_.key {
+            // Deadlines beyond the range of the timer wheel fire early, so register again for the remaining time.
+            Some(key) if self_.timer.has_fired(key) => {
+                self_.timer.remove(key);
+                self_.key = Some(self_.timer.register(self_.deadline, ctx.waker()));
+            },
+            Some(key) => self_.timer.update(key, ctx.waker()),
+            None => self_.key = Some(self_.timer.register(self_.deadline, ctx.waker())),
+        }
+        Poll::Pending
+    }
+}
+
+/// Drop Trait Implementation for Sleep Futures
+impl Drop for Sleep {
+    fn drop(&mut self) {
+        self.cancel();
+    }
+}
+
+/// Future Trait Implementation for Timeout Futures
+impl<F: Future> Future for Timeout<F> {
+    type Output = Result<F::Output, Elapsed>;
+
+    /// Polls the target [Timeout].
+    fn poll(self: Pin<&mut Self>, ctx: &mut Context) -> Poll<Self::Output> {
+        // Safety: the underlying future is never moved out of a pinned timeout, and the sleep is not pinned.
+        let self_: &mut Timeout<F> = unsafe { self.get_unchecked_mut() };
+        let future: Pin<&mut F> = unsafe { Pin::new_unchecked(&mut self_.future) };
+        if let Poll::Ready(output) = Future::poll(future, ctx) {
+            return Poll::Ready(Ok(output));
+        }
+        match Future::poll(Pin::new(&mut self_.sleep), ctx) {
+            Poll::Ready(()) => Poll::Ready(Err(Elapsed)),
+            Poll::Pending => Poll::Pending,
+        }
+    }
+}
+
+/// Future Trait Implementation for Tick Futures
+impl<'a> Future for Tick<'a> {
+    type Output = Instant;
+
+    /// Polls the target [Tick].
+    fn poll(self: Pin<&mut Self>, ctx: &mut Context) -> Poll<Instant> {
+        self.get_mut().interval.poll_tick(ctx)
+    }
+}
+
+/// Display Trait Implementation for Elapsed Errors
+impl fmt::Display for Elapsed {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "deadline has elapsed")
+    }
+}
+
+/// Error Trait Implementation for Elapsed Errors
+impl Error for Elapsed {}
+
+//==============================================================================
+// Unit Tests
+//==============================================================================
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        test_utils::TestTask,
+        timer::wheel::MAX_TICKS,
+        Elapsed,
+        Interval,
+        ManualClock,
+        Scheduler,
+        SchedulerHandle,
+        Timer,
+    };
+    use ::std::{
+        cell::{
+            Cell,
+            RefCell,
+        },
+        future,
+        rc::Rc,
+        time::{
+            Duration,
+            Instant,
+        },
+    };
+
+    fn setup() -> (Rc<ManualClock>, Scheduler) {
+        let clock: Rc<ManualClock> = Rc::new(ManualClock::new());
+        let scheduler: Scheduler = Scheduler::new(clock.clone());
+        (clock, scheduler)
+    }
+
+    #[test]
+    fn sleep_completes_at_deadline() {
+        let (clock, scheduler): (Rc<ManualClock>, Scheduler) = setup();
+        let timer: Timer = scheduler.timer();
+        let done: Rc<Cell<bool>> = Rc::new(Cell::new(false));
+        let done_: Rc<Cell<bool>> = done.clone();
+        let handle: SchedulerHandle = scheduler
+            .insert(TestTask::new(async move {
+                timer.sleep(Duration::from_millis(10)).await;
+                done_.set(true);
+            }))
+            .expect("insert() failed");
+
+        scheduler.poll();
+        clock.advance(Duration::from_millis(9));
+        scheduler.poll();
+        assert!(!done.get());
+
+        clock.advance(Duration::from_millis(1));
+        scheduler.poll();
+        assert!(done.get());
+        assert!(handle.has_completed());
+    }
+
+    #[test]
+    fn sleep_beyond_wheel_range() {
+        let (clock, scheduler): (Rc<ManualClock>, Scheduler) = setup();
+        let timer: Timer = scheduler.timer();
+        let range: Duration = Duration::from_millis(MAX_TICKS);
+        let done: Rc<Cell<bool>> = Rc::new(Cell::new(false));
+        let done_: Rc<Cell<bool>> = done.clone();
+        let _handle: SchedulerHandle = scheduler
+            .insert(TestTask::new(async move {
+                timer.sleep(range * 2).await;
+                done_.set(true);
+            }))
+            .expect("insert() failed");
+
+        // The timer wheel fires at the end of its range, but the sleep is not over yet.
+        scheduler.poll();
+        clock.advance(range);
+        scheduler.poll();
+        assert!(!done.get());
+        clock.advance(range - Duration::from_millis(1));
+        scheduler.poll();
+        assert!(!done.get());
+
+        clock.advance(Duration::from_millis(1));
+        scheduler.poll();
+        assert!(done.get());
+    }
+
+    #[test]
+    fn timeout_expires() {
+        let (clock, scheduler): (Rc<ManualClock>, Scheduler) = setup();
+        let timer: Timer = scheduler.timer();
+        let result: Rc<Cell<Option<Result<(), Elapsed>>>> = Rc::new(Cell::new(None));
+        let result_: Rc<Cell<Option<Result<(), Elapsed>>>> = result.clone();
+        let _handle: SchedulerHandle = scheduler
+            .insert(TestTask::new(async move {
+                let outcome: Result<(), Elapsed> = timer.timeout(future::pending::<()>(), Duration::from_secs(1)).await;
+                result_.set(Some(outcome));
+            }))
+            .expect("insert() failed");
+
+        scheduler.poll();
+        assert_eq!(result.get(), None);
+        clock.advance(Duration::from_secs(1));
+        scheduler.poll();
+        assert_eq!(result.get(), Some(Err(Elapsed)));
+    }
+
+    #[test]
+    fn timeout_async_block() {
+        let (clock, scheduler): (Rc<ManualClock>, Scheduler) = setup();
+        let timer: Timer = scheduler.timer();
+        let result: Rc<Cell<Option<Result<u32, Elapsed>>>> = Rc::new(Cell::new(None));
+        let result_: Rc<Cell<Option<Result<u32, Elapsed>>>> = result.clone();
+        let _handle: SchedulerHandle = scheduler
+            .insert(TestTask::new(async move {
+                // The underlying future is not Unpin.
+                let sleep: Timer = timer.clone();
+                let outcome: Result<u32, Elapsed> = timer
+                    .timeout(
+                        async move {
+                            sleep.sleep(Duration::from_millis(10)).await;
+                            42
+                        },
+                        Duration::from_secs(1),
+                    )
+                    .await;
+                result_.set(Some(outcome));
+            }))
+            .expect("insert() failed");
+
+        scheduler.poll();
+        assert_eq!(result.get(), None);
+        clock.advance(Duration::from_millis(10));
+        scheduler.poll();
+        assert_eq!(result.get(), Some(Ok(42)));
+    }
+
+    #[test]
+    fn interval_ticks() {
+        let (clock, scheduler): (Rc<ManualClock>, Scheduler) = setup();
+        let timer: Timer = scheduler.timer();
+        let start: Instant = timer.now();
+        let ticks: Rc<RefCell<Vec<Duration>>> = Rc::new(RefCell::new(Vec::new()));
+        let ticks_: Rc<RefCell<Vec<Duration>>> = ticks.clone();
+        let _handle: SchedulerHandle = scheduler
+            .insert(TestTask::new(async move {
+                let mut interval: Interval = timer.interval(Duration::from_millis(100));
+                loop {
+                    let tick: Instant = interval.tick().await;
+                    ticks_.borrow_mut().push(tick - start);
+                }
+            }))
+            .expect("insert() failed");
+
+        for _ in 0..3 {
+            scheduler.poll();
+            clock.advance(Duration::from_millis(100));
+        }
+        scheduler.poll();
+        // A missed tick is skipped.
+        clock.advance(Duration::from_millis(250));
+        scheduler.poll();
+
+        let expected: Vec<Duration> = [100, 200, 300, 400]
+            .iter()
+            .map(|ms| Duration::from_millis(*ms))
+            .collect();
+        assert_eq!(*ticks.borrow(), expected);
+        assert_eq!(
+            scheduler.timer().next_deadline(),
+            Some(start + Duration::from_millis(650))
+        );
+    }
+}
@@ -0,0 +1,383 @@
+// Copyright (c) Microsoft Corporation.
+// Licensed under the MIT license.
+
+//! Implementation of a hierarchical timer wheel.
+//!
+//! The wheel is composed by [NUM_LEVELS] levels, each of which having
+//! [SLOTS_PER_LEVEL] slots. A slot in level `l` covers `SLOTS_PER_LEVEL^l`
+//! ticks, thus a timer is placed in the lowest level whose slots are coarse
+//! enough to tell it apart from the current tick. As time advances, timers in
+//! higher levels cascade down to lower levels, until they fire at level zero.
+
+//==============================================================================
+// Imports
+//==============================================================================
+
+use ::std::{
+    mem,
+    task::Waker,
+};
+
+//==============================================================================
+// Constants
+//==============================================================================
+
+/// Log2 of [SLOTS_PER_LEVEL].
+const LEVEL_SHIFT: usize = 6;
+
+/// Number of Slots in a Level
+const SLOTS_PER_LEVEL: usize = 1 << LEVEL_SHIFT;
+
+/// Number of Levels in the Wheel
+const NUM_LEVELS: usize = 6;
+
+/// Maximum Number of Ticks that a Timer may be Scheduled Ahead
+pub const MAX_TICKS: u64 = (1 << (LEVEL_SHIFT * NUM_LEVELS)) - 1;
+
+//==============================================================================
+// Structures
+//==============================================================================
+
+/// A timer that is stored in the [TimerWheel].
+struct Entry {
+    /// Tick at which the timer expires.
+    deadline: u64,
+    /// Waker of the task that waits on the timer.
+    waker: Option<Waker>,
+    /// Flags whether or not the timer has fired.
+    fired: bool,
+    /// Level and slot in which the timer is currently placed.
+    location: Option<(usize, usize)>,
+}
+
+/// A level of the [TimerWheel].
+struct Level {
+    /// Flags which slots of this level hold some timer.
+    occupied: u64,
+    /// Keys of the timers that are placed in each slot.
+    slots: Vec<Vec<usize>>,
+}
+
+/// Hierarchical Timer Wheel
+pub struct TimerWheel {
+    /// Last tick that was processed.
+    elapsed: u64,
+    /// Levels of the wheel.
+    levels: Vec<Level>,
+    /// Storage for timers.
+    entries: Vec<Option<Entry>>,
+    /// Keys of vacant entries.
+    vacant: Vec<usize>,
+}
+
+//==============================================================================
+// Associate Functions
+//==============================================================================
+
+/// Associate Functions for Levels
+impl Level {
+    /// Creates an empty level.
+    fn new() -> Self {
+        Self {
+            occupied: 0,
+            slots: (0..SLOTS_PER_LEVEL).map(|_| Vec::new()).collect(),
+        }
+    }
+
+    /// Finds the next occupied slot of the `level`th level, as seen from the `elapsed` tick. The index of the slot and
+    /// the tick at which it starts are returned.
+    fn next_occupied(&self, level: usize, elapsed: u64) -> Option<(usize, u64)> {
+        if self.occupied == 0 {
+            return None;
+        }
+        let slot_range: u64 = 1 << (LEVEL_SHIFT * level);
+        let level_range: u64 = slot_range << LEVEL_SHIFT;
+        let now_slot: usize = ((elapsed / slot_range) as usize) & (SLOTS_PER_LEVEL - 1);
+        let distance: usize = self.occupied.rotate_right(now_slot as u32).trailing_zeros() as usize;
+        let slot: usize = (now_slot + distance) & (SLOTS_PER_LEVEL - 1);
+        let level_start: u64 = elapsed & !(level_range - 1);
+        let mut start: u64 = level_start + (slot as u64) * slot_range;
+        // The slot lies in the next rotation of this level.
+        if start + slot_range <= elapsed {
+            start += level_range;
+        }
+        Some((slot, start))
+    }
+}
+
+/// Associate Functions for Timer Wheels
+impl TimerWheel {
+    /// Creates an empty timer wheel.
+    pub fn new() -> Self {
+        Self {
+            elapsed: 0,
+            levels: (0..NUM_LEVELS).map(|_| Level::new()).collect(),
+            entries: Vec::new(),
+            vacant: Vec::new(),
+        }
+    }
+
+    /// Inserts a timer that expires at the `deadline` tick, returning a key that may be used to drive it. Deadlines
+    /// that lie beyond [MAX_TICKS] ahead are clamped, thus such a timer fires early and should be inserted again.
+    pub fn insert(&mut self, deadline: u64, waker: Waker) -> usize {
+        // The deadline must not wrap around to the slot of the top level that is being processed.
+        let top_shift: usize = LEVEL_SHIFT * (NUM_LEVELS - 1);
+        let latest: u64 = ((self.elapsed >> top_shift) << top_shift) + MAX_TICKS;
+        let deadline: u64 = deadline.clamp(self.elapsed + 1, latest);
+        let entry: Entry = Entry {
+            deadline,
+            waker: Some(waker),
+            fired: false,
+            location: None,
+        };
+        let key: usize = match self.vacant.pop() {
+            Some(key) => {
+                self.entries[key] = Some(entry);
+                key
+            },
+            None => {
+                self.entries.push(Some(entry));
+                self.entries.len() - 1
+            },
+        };
+        self.place(key);
+        key
+    }
+
+    /// Replaces the waker that is associated with the timer `key`.
+    pub fn update(&mut self, key: usize, waker: &Waker) {
+        if let Some(entry) = self.entries.get_mut(key).and_then(Option::as_mut) {
+            match entry.waker {
+                Some(ref old) if old.will_wake(waker) => (),
+                _ => entry.waker = Some(waker.clone()),
+            }
+        }
+    }
+
+    /// Queries whether or not the timer `key` has fired.
+    pub fn has_fired(&self, key: usize) -> bool {
+        match self.entries.get(key) {
+            Some(Some(entry)) => entry.fired,
+            _ => false,
+        }
+    }
+
+    /// Removes the timer `key` from the target [TimerWheel].
+    pub fn remove(&mut self, key: usize) {
+        let entry: Entry = match self.entries.get_mut(key).and_then(Option::take) {
+            Some(entry) => entry,
+            None => return,
+        };
+        if let Some((level, slot)) = entry.location {
+            let level: &mut Level = &mut self.levels[level];
+            level.slots[slot].retain(|k| *k != key);
+            if level.slots[slot].is_empty() {
+                level.occupied &= !(1 << slot);
+            }
+        }
+        self.vacant.push(key);
+    }
+
+    /// Queries whether or not no timer is placed in the target [TimerWheel].
+    pub fn is_empty(&self) -> bool {
+        self.levels.iter().all(|level| level.occupied == 0)
+    }
+
+    /// Returns the tick at which the next timer in the target [TimerWheel] expires.
+    pub fn next_expiration(&self) -> Option<u64> {
+        let (level, slot, _): (usize, usize, u64) = self.next_slot()?;
+        // All timers in the first occupied slot expire before any other timer.
+        self.levels[level].slots[slot]
+            .iter()
+            .filter_map(|key| self.entries[*key].as_ref())
+            .map(|entry| entry.deadline)
+            .min()
+    }
+
+    /// Advances the target [TimerWheel] up to the `now` tick. The wakers of timers that fired are pushed to `fired`
+    /// in the order of their deadlines.
+    pub fn advance(&mut self, now: u64, fired: &mut Vec<Waker>) {
+        while let Some((level, slot, start)) = self.next_slot() {
+            if start > now {
+                break;
+            }
+            self.elapsed = self.elapsed.max(start);
+            let keys: Vec<usize> = mem::take(&mut self.levels[level].slots[slot]);
+            self.levels[level].occupied &= !(1 << slot);
+            for key in keys {
+                let entry: &mut Entry = self.entries[key].as_mut().expect("timer should be stored");
+                entry.location = None;
+                if entry.deadline <= self.elapsed {
+                    entry.fired = true;
+                    if let Some(waker) = entry.waker.take() {
+                        fired.push(waker);
+                    }
+                } else {
+                    // Cascade timer down to a lower level.
+                    self.place(key);
+                }
+            }
+        }
+        self.elapsed = self.elapsed.max(now);
+    }
+
+    /// Finds the first occupied slot in the target [TimerWheel]. The level and index of the slot are returned, as
+    /// well as the tick at which it starts.
+    fn next_slot(&self) -> Option<(usize, usize, u64)> {
+        // Lower levels always expire before higher ones.
+        for (level_ix, level) in self.levels.iter().enumerate() {
+            if let Some((slot, start)) = level.next_occupied(level_ix, self.elapsed) {
+                return Some((level_ix, slot, start));
+            }
+        }
+        None
+    }
+
+    /// Places the timer `key` in the slot that matches its deadline.
+    fn place(&mut self, key: usize) {
+        let entry: &mut Entry = self.entries[key].as_mut().expect("timer should be stored");
+        let masked: u64 = (self.elapsed ^ entry.deadline) | (SLOTS_PER_LEVEL as u64 - 1);
+        let significant: usize = 63 - masked.leading_zeros() as usize;
+        let level: usize = (significant / LEVEL_SHIFT).min(NUM_LEVELS - 1);
+        let slot: usize = ((entry.deadline >> (LEVEL_SHIFT * level)) as usize) & (SLOTS_PER_LEVEL - 1);
+        entry.location = Some((level, slot));
+        self.levels[level].slots[slot].push(key);
+        self.levels[level].occupied |= 1 << slot;
+    }
+}
+
+//==============================================================================
+// Trait Implementations
+//==============================================================================
+
+/// Default Trait Implementation for Timer Wheels
+impl Default for TimerWheel {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+//==============================================================================
+// Unit Tests
+//==============================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        TimerWheel,
+        MAX_TICKS,
+    };
+    use crate::rng::Rng;
+    use ::std::{
+        sync::{
+            Arc,
+            Mutex,
+        },
+        task::{
+            Wake,
+            Waker,
+        },
+    };
+    use ::test::{
+        black_box,
+        Bencher,
+    };
+
+    /// Waker that records its identifier in a shared log when woken.
+    struct LogWaker {
+        id: u64,
+        log: Arc<Mutex<Vec<u64>>>,
+    }
+
+    impl Wake for LogWaker {
+        fn wake(self: Arc<Self>) {
+            self.log.lock().unwrap().push(self.id);
+        }
+    }
+
+    fn log_waker(id: u64, log: &Arc<Mutex<Vec<u64>>>) -> Waker {
+        Waker::from(Arc::new(LogWaker { id, log: log.clone() }))
+    }
+
+    fn advance(wheel: &mut TimerWheel, now: u64) {
+        let mut fired: Vec<Waker> = Vec::new();
+        wheel.advance(now, &mut fired);
+        fired.into_iter().for_each(Waker::wake);
+    }
+
+    #[test]
+    fn wheel_fires_in_order() {
+        let log: Arc<Mutex<Vec<u64>>> = Arc::new(Mutex::new(Vec::new()));
+        let mut wheel: TimerWheel = TimerWheel::new();
+
+        // Deadlines spread across several levels.
+        let deadlines: [u64; 6] = [300_000, 5, 64, 4_095, 63, 4_096];
+        let keys: Vec<usize> = deadlines
+            .iter()
+            .map(|deadline| wheel.insert(*deadline, log_waker(*deadline, &log)))
+            .collect();
+
+        advance(&mut wheel, 4_095);
+        assert_eq!(*log.lock().unwrap(), vec![5, 63, 64, 4_095]);
+        assert!(wheel.has_fired(keys[1]));
+        assert!(!wheel.has_fired(keys[5]));
+        assert_eq!(wheel.next_expiration(), Some(4_096));
+
+        advance(&mut wheel, MAX_TICKS);
+        assert_eq!(*log.lock().unwrap(), vec![5, 63, 64, 4_095, 4_096, 300_000]);
+        assert_eq!(wheel.next_expiration(), None);
+    }
+
+    #[test]
+    fn wheel_remove() {
+        let log: Arc<Mutex<Vec<u64>>> = Arc::new(Mutex::new(Vec::new()));
+        let mut wheel: TimerWheel = TimerWheel::new();
+
+        let a: usize = wheel.insert(10, log_waker(10, &log));
+        let b: usize = wheel.insert(10, log_waker(11, &log));
+        wheel.remove(a);
+        advance(&mut wheel, 10);
+        assert_eq!(*log.lock().unwrap(), vec![11]);
+        assert!(wheel.has_fired(b));
+
+        // Vacant entries are reused.
+        wheel.remove(b);
+        assert_eq!(wheel.insert(20, log_waker(20, &log)), b);
+    }
+
+    #[test]
+    fn wheel_random_deadlines() {
+        let log: Arc<Mutex<Vec<u64>>> = Arc::new(Mutex::new(Vec::new()));
+        let mut wheel: TimerWheel = TimerWheel::new();
+        let mut rng: Rng = Rng::new(0);
+        let mut now: u64 = 0;
+
+        for _ in 0..64 {
+            let deadline: u64 = now + 1 + rng.gen_range(999_999) as u64;
+            wheel.insert(deadline, log_waker(deadline, &log));
+            now += rng.gen_range(10_000) as u64;
+            advance(&mut wheel, now);
+        }
+        advance(&mut wheel, now + 1_000_000);
+
+        // All timers fire, in the order of their deadlines.
+        let fired: Vec<u64> = log.lock().unwrap().clone();
+        let mut sorted: Vec<u64> = fired.clone();
+        sorted.sort();
+        assert_eq!(fired.len(), 64);
+        assert_eq!(fired, sorted);
+    }
+
+    #[bench]
+    fn bench_wheel_insert_remove(b: &mut Bencher) {
+        let log: Arc<Mutex<Vec<u64>>> = Arc::new(Mutex::new(Vec::new()));
+        let mut wheel: TimerWheel = TimerWheel::new();
+        let waker: Waker = log_waker(0, &log);
+        let deadline: u64 = 1 + Rng::new(0).gen_range(MAX_TICKS as usize - 1) as u64;
+
+        b.iter(|| {
+            let key: usize = wheel.insert(black_box(deadline), waker.clone());
+            wheel.remove(key);
+        });
+    }
+}